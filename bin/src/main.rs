@@ -1,9 +1,17 @@
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
 
-use cuberithm::solver;
-use cuberithm::{cube::CubeState, solution::Solution};
+use cuberithm::auf::Auf;
+use cuberithm::cube::CubeState;
+use cuberithm::metric::Metric;
+use cuberithm::pattern::CubePattern;
+use cuberithm::solver::{IdaCache, Method};
+use cuberithm::{optimal, scramble, solver, tables};
+use std::fmt::Display;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::{collections::BTreeSet, time::Instant};
+use std::time::{Duration, Instant};
 
 #[cfg(unix)]
 #[global_allocator]
@@ -12,52 +20,389 @@ static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 /// Simple algorithm generator for a 3x3x3 Rubik's Cube
 ///
 /// Format of states passed in arguments is a 54 character long string composed of:
-/// characters: Y (yellow), B (blue), G (green), R (red), W (white), O (orange)
+/// characters: Y (yellow), B (blue), G (green), R (red), W (white), O (orange),
+///             N (unspecified, in the desired state it matches any color)
+/// a sticker of the desired state can also be a set of colors it can have, e.g. [WY] for
+/// white or yellow and [^Y] for any color but yellow
 /// arranged from left to right, bottom to top, in the order of faces:
 /// white -> orange -> green -> red -> blue -> yellow
 /// example: WWWWWWWWWOOOOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBYYYYYYYYY (solved cube)
 ///          WWWWWWWWWOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBOOOYYYYYYYYY (after U move)
 ///          WWWWWWWWWOOOOOOOOOGGGGGGGRRRRRRRRBGGBBBBBBRBBYYYYYYYYY (after J-Perm)
+///
+/// States can also be given as a scramble applied to the solved cube, e.g. --initial-scramble "R U R' U'"
+/// Desired states can be repeated, or read from a file with one on each line, to find algorithms
+/// reaching any of them
 #[derive(Parser)]
-#[command(version, about, verbatim_doc_comment)]
+#[command(
+    version,
+    about,
+    verbatim_doc_comment,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    solve: Option<SolveArgs>,
+    /// Directory the solvers keep their tables in, instead of the user's cache directory
+    #[arg(long, global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Build the solvers' tables on every run instead of keeping them
+    #[arg(long, global = true, conflicts_with = "cache_dir")]
+    no_cache: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generates scrambles instead of algorithms
+    Scramble(ScrambleArgs),
+    /// Finds the fewest face turns solving a state
+    Optimal(OptimalArgs),
+}
+
+#[derive(clap::Args)]
+struct SolveArgs {
     /// Initial Cube state, right->left bottom->top green on front white on top
-    #[arg(short, long)]
-    initial_state: String,
-    /// Desired Cube state
-    #[arg(short, long)]
-    desired_state: String,
-    /// Min moves for algorithms to be generated
+    #[arg(short, long, required_unless_present = "initial_scramble")]
+    initial_state: Option<String>,
+    /// Desired Cube state, can be repeated to accept any of them, held any way when the slice
+    /// or wide moves can move the centers
+    #[arg(short, long, required_unless_present_any = ["desired_scramble", "goals_file"])]
+    desired_state: Vec<String>,
+    /// Algorithm applied to the solved cube to get the initial state, e.g. "R U R' U'"
+    #[arg(long, conflicts_with = "initial_state")]
+    initial_scramble: Option<String>,
+    /// Algorithm applied to the solved cube to get a desired state, can be repeated
+    #[arg(long)]
+    desired_scramble: Vec<String>,
+    /// File with a desired state on each line, skipping empty lines and lines starting with #
+    #[arg(long)]
+    goals_file: Option<PathBuf>,
+    /// Min moves for algorithms to be generated, counted in the chosen metric
     #[arg(long)]
     min_moves: u8,
-    /// Max moves for algorithms to be generated
+    /// Max moves for algorithms to be generated, counted in the chosen metric
     #[arg(long)]
     max_moves: u8,
     // Max difference between shortest algorithm and the longest
     #[arg(short, long)]
     threshold: u8,
+    /// Also generate algorithms with M, E and S slice moves, every one of them counting as
+    /// a single move unless another metric is chosen
+    #[arg(long)]
+    slice_moves: bool,
+    /// Also generate algorithms with wide moves (r, u, f, l, b, d)
+    #[arg(long)]
+    wide_moves: bool,
+    /// Metric the moves are counted in: htm, qtm, stm, etm or qstm, htm by default or stm
+    /// with slice moves
+    #[arg(long)]
+    metric: Option<Metric>,
+    /// U layer adjustments done for free before or after the algorithms: none, pre, post or both
+    #[arg(long, default_value_t = Auf::None)]
+    auf: Auf,
+    /// Search method: bidirectional (fastest) or ida (almost no memory), by default ida when
+    /// the desired scramble has unspecified stickers or sets of colors and bidirectional otherwise
+    #[arg(long)]
+    method: Option<Method>,
+    /// Memory in MiB the bidirectional method keeps the states reached halfway in at most,
+    /// searching less from the initial state or using ida instead when they don't fit, and always
+    /// with unspecified stickers or sets of colors in the desired scramble
+    #[arg(long)]
+    max_memory: Option<usize>,
+    /// Seconds the search runs for at most, printing the algorithms found until then
+    #[arg(long, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+    /// Stop after finding this many algorithms
+    #[arg(long)]
+    max_solutions: Option<usize>,
+}
+
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds
+        .parse()
+        .map_err(|err: std::num::ParseFloatError| err.to_string())?;
+    Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string())
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ScrambleKind {
+    /// Uniformly random state, like competition scrambles
+    RandomState,
+    /// Random moves, out of the subset if there is one
+    RandomMoves,
+    /// Uniformly random last layer case with the first two layers solved
+    LastLayer,
+}
+
+#[derive(clap::Args)]
+struct ScrambleArgs {
+    /// Kind of scramble
+    #[arg(long, value_enum, default_value_t = ScrambleKind::RandomState)]
+    kind: ScrambleKind,
+    /// Amount of moves of random moves scrambles
+    #[arg(long, default_value_t = 25)]
+    length: usize,
+    /// Moves random moves scrambles are made of, e.g. "<R,U>", every face turn by default
+    #[arg(long)]
+    subset: Option<String>,
+    /// Seed giving the same scrambles every time
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Amount of scrambles
+    #[arg(short = 'n', long, default_value_t = 1)]
+    count: usize,
+}
+
+#[derive(clap::Args)]
+struct OptimalArgs {
+    /// Cube state to solve
+    #[arg(short, long, required_unless_present = "initial_scramble")]
+    initial_state: Option<String>,
+    /// Algorithm applied to the solved cube to get the state to solve
+    #[arg(long, conflicts_with = "initial_state")]
+    initial_scramble: Option<String>,
+    /// Print every solution of the optimal length instead of the first one found
+    #[arg(long)]
+    all: bool,
+    /// Give up on solutions longer than this
+    #[arg(long, default_value_t = 20)]
+    max_length: u8,
+}
+
+/// Reads a state or pattern either from its stickers or from a scramble, exits on invalid input
+/// or when the stickers it fixes can't be reached from the solved cube
+fn parse_state<T>(
+    state: Option<&str>,
+    scramble: Option<&str>,
+    fixed_stickers: fn(&T) -> CubeState,
+) -> T
+where
+    T: FromStr + From<CubeState>,
+    T::Err: Display,
+{
+    let result = match (state, scramble) {
+        (Some(state), _) => T::from_str(state).map_err(|err| err.to_string()),
+        (_, Some(scramble)) => CubeState::from_scramble(scramble)
+            .map(T::from)
+            .map_err(|err| err.to_string()),
+        (None, None) => unreachable!("clap requires one of them"),
+    }
+    .and_then(|state| {
+        fixed_stickers(&state)
+            .validate()
+            .map(|_| state)
+            .map_err(|err| format!("Invalid state: {}", err))
+    });
+
+    result.unwrap_or_else(|err| Args::command().error(ErrorKind::InvalidValue, err).exit())
+}
+
+fn print_scrambles(args: &ScrambleArgs) {
+    let moves = match &args.subset {
+        Some(subset) => scramble::parse_subset(subset).unwrap_or_else(|err| {
+            Args::command()
+                .error(ErrorKind::InvalidValue, format!("Invalid subset: {}", err))
+                .exit()
+        }),
+        None => scramble::face_turns(),
+    };
+
+    let mut rng = scramble::rng(args.seed);
+    for _ in 0..args.count {
+        let scramble = match args.kind {
+            ScrambleKind::RandomState => scramble::random_state_with(&mut rng),
+            ScrambleKind::RandomMoves => scramble::random_moves_with(&mut rng, args.length, &moves),
+            ScrambleKind::LastLayer => scramble::last_layer_with(&mut rng),
+        };
+        println!("{}", scramble);
+    }
+}
+
+fn print_optimal(args: &OptimalArgs) {
+    let state: CubeState = parse_state(
+        args.initial_state.as_deref(),
+        args.initial_scramble.as_deref(),
+        |state| *state,
+    );
+
+    let initial_time = Instant::now();
+    let result = optimal::solve()
+        .state(state)
+        .all_solutions(args.all)
+        .max_length(args.max_length)
+        .multi_threaded(true)
+        .call()
+        .unwrap_or_else(|err| {
+            Args::command()
+                .error(ErrorKind::ValueValidation, err.to_string())
+                .exit()
+        });
+    let elapsed_time = Instant::now().duration_since(initial_time);
+
+    for (idx, solution) in result.solutions.iter().enumerate() {
+        println!("Solution {}: {}", idx, solution);
+    }
+    println!("\nOptimal Length: {} HTM", result.length);
+    println!("Elapsed Time: {:.3}s", elapsed_time.as_secs_f64());
 }
 
 fn main() {
     let args = Args::parse();
+    if args.no_cache {
+        tables::set_cache_dir(None);
+    } else if let Some(cache_dir) = args.cache_dir {
+        tables::set_cache_dir(Some(cache_dir));
+    }
 
-    let initial_state = CubeState::from_str(&args.initial_state).unwrap();
-    let desired_state = CubeState::from_str(&args.desired_state).unwrap();
+    match (args.command, args.solve) {
+        (Some(Command::Scramble(args)), _) => print_scrambles(&args),
+        (Some(Command::Optimal(args)), _) => print_optimal(&args),
+        (None, Some(args)) => solve(args),
+        (None, None) => unreachable!("clap requires the solve arguments without a subcommand"),
+    }
+}
+
+fn solve(args: SolveArgs) {
+    let initial_state = parse_state(
+        args.initial_state.as_deref(),
+        args.initial_scramble.as_deref(),
+        |state| *state,
+    );
+
+    let goals_file = args.goals_file.as_ref().map(|path| {
+        fs::read_to_string(path).unwrap_or_else(|err| {
+            Args::command()
+                .error(
+                    ErrorKind::Io,
+                    format!("Can't read {}: {}", path.display(), err),
+                )
+                .exit()
+        })
+    });
+    let goals = goals_file
+        .iter()
+        .flat_map(|goals| goals.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let mut desired_states: Vec<CubePattern> = args
+        .desired_state
+        .iter()
+        .map(String::as_str)
+        .chain(goals)
+        .map(|state| parse_state(Some(state), None, CubePattern::relaxed))
+        .chain(
+            args.desired_scramble
+                .iter()
+                .map(|scramble| parse_state(None, Some(scramble), CubePattern::relaxed)),
+        )
+        .collect();
+    if !args.slice_moves && !args.wide_moves {
+        // centers can't move, so the desired states were just typed holding the cube differently
+        for desired_state in desired_states.iter_mut() {
+            *desired_state = desired_state
+                .oriented_like(&initial_state)
+                .unwrap_or(*desired_state);
+        }
+    }
+
+    let goal_count = desired_states.len();
+    desired_states.retain(|desired_state| {
+        initial_state.can_reach(
+            &desired_state.relaxed(),
+            args.slice_moves || args.wide_moves,
+        )
+    });
+    if desired_states.is_empty() {
+        let message = if goal_count == 1 {
+            "The desired state is unreachable from the initial state"
+        } else {
+            "None of the desired states is reachable from the initial state"
+        };
+        Args::command()
+            .error(ErrorKind::ValueValidation, message)
+            .exit();
+    }
+    if desired_states.len() < goal_count {
+        eprintln!(
+            "Skipping {} desired states unreachable from the initial state",
+            goal_count - desired_states.len()
+        );
+    }
+    if args.slice_moves || args.wide_moves {
+        // the algorithms are searched up to a cube rotation at the end, so any way of holding
+        // the desired states is as good
+        let mut oriented = Vec::with_capacity(desired_states.len() * 24);
+        for desired_state in desired_states.iter().flat_map(|it| it.orientations()) {
+            if !oriented.contains(&desired_state) {
+                oriented.push(desired_state);
+            }
+        }
+        desired_states = oriented;
+    }
+    let metric = args.metric.unwrap_or(if args.slice_moves {
+        Metric::Stm
+    } else {
+        Metric::Htm
+    });
     let min_moves = args.min_moves;
     let max_moves = args.max_moves;
     let threshold = args.threshold;
 
     let initial_time = Instant::now();
 
-    let mut solutions: BTreeSet<Solution> = BTreeSet::new();
+    let ida_cache = IdaCache::new();
+    let deadline = args.timeout.map(|timeout| initial_time + timeout);
+    let mut solution_count = 0;
+    let mut complete = true;
 
     let mut since_found = 0;
     for i in min_moves..=max_moves {
-        let found_solutions = solver::solve(initial_state, desired_state, i, true);
-
-        solutions.extend(found_solutions);
+        // every algorithm found costs i moves, so printing them as they come keeps them
+        // ordered by length
+        let flow = solver::solve_any_each()
+            .initial_state(initial_state)
+            .desired_states(&desired_states)
+            .move_count(i)
+            .multi_threaded(true)
+            .slice_moves(args.slice_moves)
+            .wide_moves(args.wide_moves)
+            .metric(metric)
+            .auf(args.auf)
+            .maybe_method(args.method)
+            .ida_cache(&ida_cache)
+            // more than fits in memory anyway is as good as no limit
+            .maybe_max_memory(args.max_memory.map(|mib| mib.saturating_mul(1 << 20)))
+            .maybe_timeout(
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+            )
+            .maybe_max_solutions(args.max_solutions.map(|max| max - solution_count))
+            .on_solution(|solution| {
+                let (pre, post) = args
+                    .auf
+                    .adjustments(initial_state, &desired_states, &solution)
+                    .unwrap_or_default();
+                println!(
+                    "Solution {}: {}{}{} ({} {})",
+                    solution_count,
+                    pre.map(|rot| format!("({}) ", rot)).unwrap_or_default(),
+                    solution,
+                    post.map(|rot| format!(" ({})", rot)).unwrap_or_default(),
+                    solution.len_in(metric),
+                    metric
+                );
+                solution_count += 1;
+                ControlFlow::Continue(())
+            })
+            .call();
+        if flow.is_break() {
+            complete = false;
+            break;
+        }
 
-        if since_found > 0 || !solutions.is_empty() {
+        if since_found > 0 || solution_count > 0 {
             since_found += 1;
         }
 
@@ -68,15 +413,12 @@ fn main() {
 
     let elapsed_time = Instant::now().duration_since(initial_time);
 
-    for (idx, solution) in (0_u16..).zip(solutions.iter()) {
-        print!("Solution {}: ", idx);
-        for rot in solution {
-            print!("{} ", rot);
-        }
-        println!();
+    if complete {
+        println!("\nDone.");
+    } else {
+        println!("\nStopped early, there may be more algorithms.");
     }
-    println!("\nDone.");
 
     println!("Elapsed Time: {:.3}s", elapsed_time.as_secs_f64());
-    println!("Solutions Found: {}", solutions.len());
+    println!("Solutions Found: {}", solution_count);
 }
@@ -0,0 +1,283 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Deref};
+use std::str::FromStr;
+
+use crate::cube::CubeState;
+use crate::face::Axis;
+use crate::metric::Metric;
+use crate::parser::{self, ParseErr};
+use crate::rotation::Rotation;
+
+/// A sequence of rotations
+///
+/// Algorithms are ordered by length first and then move by move, so sorting a set of them
+/// lists the shortest ones first
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Algorithm(Vec<Rotation>);
+
+impl Algorithm {
+    pub fn new() -> Self {
+        Algorithm(Vec::new())
+    }
+
+    pub fn push(&mut self, rot: Rotation) {
+        self.0.push(rot);
+    }
+
+    pub fn rotations(&self) -> &[Rotation] {
+        &self.0
+    }
+
+    /// Returns the algorithm undoing this one
+    pub fn inverse(&self) -> Algorithm {
+        self.0.iter().rev().map(|rot| rot.reverse()).collect()
+    }
+
+    /// Returns the algorithm mirrored from left to right, e.g. `R U R'` becomes `L' U' L`
+    pub fn mirror_lr(&self) -> Algorithm {
+        self.0.iter().map(|rot| rot.mirror(Axis::X)).collect()
+    }
+
+    /// Returns the algorithm mirrored from front to back, e.g. `F R F'` becomes `B' R' B`
+    pub fn mirror_fb(&self) -> Algorithm {
+        self.0.iter().map(|rot| rot.mirror(Axis::Z)).collect()
+    }
+
+    /// Returns the state reached by applying the algorithm to the given state
+    pub fn apply_to(&self, state: CubeState) -> CubeState {
+        self.0.iter().fold(state, |state, &rot| state.rotate(rot))
+    }
+
+    /// Returns the algorithm with every redundant move removed
+    ///
+    /// Consecutive moves on the same axis commute, so they are merged by the layers they turn,
+    /// e.g. `R R` becomes `R2`, `R R'` disappears and `R L R'` becomes `L`. Whenever that empties
+    /// a group of moves, the ones around it get merged as well, e.g. `R U U' R'` disappears
+    pub fn simplify(&self) -> Algorithm {
+        self.simplify_by(merge_moves)
+    }
+
+    /// Like simplify, but also cancels moves turning the same layers in a different way, using the
+    /// fewest moves for each group, e.g. `r R'` becomes `M'` and `R M' L'` becomes `x`
+    pub fn simplify_layers(&self) -> Algorithm {
+        self.simplify_by(merge_layers)
+    }
+
+    fn simplify_by(&self, merge: fn(&[Rotation], Rotation) -> Vec<Rotation>) -> Algorithm {
+        let mut result: Vec<Rotation> = Vec::with_capacity(self.0.len());
+        for &rot in &self.0 {
+            let run_len = result
+                .iter()
+                .rev()
+                .take_while(|prev| prev.axis() == rot.axis())
+                .count();
+            let run = result.split_off(result.len() - run_len);
+            result.extend(merge(&run, rot));
+        }
+        Algorithm(result)
+    }
+
+    /// Length of the algorithm in the given metric
+    pub fn len_in(&self, metric: Metric) -> u32 {
+        metric.count(&self.0)
+    }
+}
+
+/// Merges a rotation into moves on its same axis, adding up the turns of equal moves
+fn merge_moves(run: &[Rotation], rot: Rotation) -> Vec<Rotation> {
+    let mut turns: Vec<(u8, i8)> = Vec::with_capacity(run.len() + 1);
+    for prev in run.iter().chain([&rot]) {
+        match turns
+            .iter_mut()
+            .find(|(layers, _)| *layers == prev.layers())
+        {
+            Some((_, t)) => *t += prev.turns(),
+            None => turns.push((prev.layers(), prev.turns())),
+        }
+    }
+
+    turns
+        .into_iter()
+        .filter_map(|(layers, turns)| Rotation::from_layers(rot.axis(), layers, turns))
+        .collect()
+}
+
+/// Merges a rotation into moves on its same axis by adding up the turns of every single layer,
+/// then writes those with the fewest moves, preferring the ones without cube rotations
+fn merge_layers(run: &[Rotation], rot: Rotation) -> Vec<Rotation> {
+    let merged = merge_moves(run, rot);
+
+    let mut layer_turns = [0i8; 3];
+    for prev in &merged {
+        for (layer, turns) in layer_turns.iter_mut().enumerate() {
+            if prev.layers() & (1 << layer) != 0 {
+                *turns += prev.turns();
+            }
+        }
+    }
+
+    // every way of writing them is some amount of turns of the cube and of both wide moves,
+    // with the single layers making up the rest
+    let mut best: Option<Vec<Rotation>> = None;
+    for (cube, wide_low, wide_high) in (0..64).map(|i| (i & 3, (i >> 2) & 3, i >> 4)) {
+        let mut remaining = layer_turns;
+        let wide_moves = [(0b110, wide_high), (0b011, wide_low), (0b111, cube)];
+        for (layers, turns) in wide_moves {
+            for (layer, remaining) in remaining.iter_mut().enumerate() {
+                if layers & (1 << layer) != 0 {
+                    *remaining -= turns;
+                }
+            }
+        }
+
+        let single_moves = [
+            (0b100, remaining[2]),
+            (0b001, remaining[0]),
+            (0b010, remaining[1]),
+        ];
+        let moves: Vec<Rotation> = single_moves
+            .into_iter()
+            .chain(wide_moves)
+            .filter_map(|(layers, turns)| Rotation::from_layers(rot.axis(), layers, turns))
+            .collect();
+
+        let key = |moves: &[Rotation]| (moves.len(), moves.iter().any(|m| m.is_cube_rotation()));
+        if best.as_ref().is_none_or(|best| key(&moves) < key(best)) {
+            best = Some(moves);
+        }
+    }
+
+    // keep the moves as they were written unless there is a shorter way
+    let best = best.unwrap();
+    if best.len() < merged.len() {
+        best
+    } else {
+        merged
+    }
+}
+
+impl Deref for Algorithm {
+    type Target = [Rotation];
+
+    fn deref(&self) -> &[Rotation] {
+        &self.0
+    }
+}
+
+impl From<Vec<Rotation>> for Algorithm {
+    fn from(rotations: Vec<Rotation>) -> Self {
+        Algorithm(rotations)
+    }
+}
+
+impl From<&[Rotation]> for Algorithm {
+    fn from(rotations: &[Rotation]) -> Self {
+        Algorithm(rotations.to_vec())
+    }
+}
+
+impl From<Algorithm> for Vec<Rotation> {
+    fn from(alg: Algorithm) -> Self {
+        alg.0
+    }
+}
+
+impl FromIterator<Rotation> for Algorithm {
+    fn from_iter<I: IntoIterator<Item = Rotation>>(iter: I) -> Self {
+        Algorithm(iter.into_iter().collect())
+    }
+}
+
+impl Extend<Rotation> for Algorithm {
+    fn extend<I: IntoIterator<Item = Rotation>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for Algorithm {
+    type Item = Rotation;
+    type IntoIter = std::vec::IntoIter<Rotation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Algorithm {
+    type Item = &'a Rotation;
+    type IntoIter = std::slice::Iter<'a, Rotation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Add<&Algorithm> for Algorithm {
+    type Output = Algorithm;
+
+    fn add(mut self, other: &Algorithm) -> Algorithm {
+        self += other;
+        self
+    }
+}
+
+impl Add for Algorithm {
+    type Output = Algorithm;
+
+    fn add(self, other: Algorithm) -> Algorithm {
+        self + &other
+    }
+}
+
+impl AddAssign<&Algorithm> for Algorithm {
+    fn add_assign(&mut self, other: &Algorithm) {
+        self.0.extend_from_slice(&other.0);
+    }
+}
+
+impl AddAssign for Algorithm {
+    fn add_assign(&mut self, other: Algorithm) {
+        self.0.extend(other.0);
+    }
+}
+
+impl Ord for Algorithm {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for Algorithm {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Moves separated by spaces, the alternate flag ({:#}) writes wide moves as Rw
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, rot) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " ")?;
+            }
+            if f.alternate() {
+                write!(f, "{:#}", rot)?;
+            } else {
+                write!(f, "{}", rot)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = ParseErr;
+
+    fn from_str(alg: &str) -> Result<Algorithm, Self::Err> {
+        parser::parse_algorithm(alg)
+    }
+}
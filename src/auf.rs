@@ -0,0 +1,126 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::cube::CubeState;
+use crate::face::Axis;
+use crate::pattern::CubePattern;
+use crate::rotation::Rotation;
+use crate::solution::Solution;
+
+/// Adjustments of the U layer, not turning it first
+const U_TURNS: [Option<Rotation>; 4] = [
+    None,
+    Some(Rotation::U),
+    Some(Rotation::Up),
+    Some(Rotation::U2),
+];
+
+/// Adjustments of the U layer (AUF) done for free before or after an algorithm,
+/// so that every U turn of the initial or desired state is the same case
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Auf {
+    /// Only the given states
+    #[default]
+    None,
+    /// Any U turn of the initial state
+    Pre,
+    /// Any U turn of the desired state
+    Post,
+    /// Any U turn of both
+    Both,
+}
+
+impl Auf {
+    /// U turns that can be done before the algorithm, None being no turn
+    pub fn pre_turns(&self) -> &'static [Option<Rotation>] {
+        match self {
+            Auf::Pre | Auf::Both => &U_TURNS,
+            Auf::None | Auf::Post => &U_TURNS[..1],
+        }
+    }
+
+    /// U turns that can be done after the algorithm, None being no turn
+    pub fn post_turns(&self) -> &'static [Option<Rotation>] {
+        match self {
+            Auf::Post | Auf::Both => &U_TURNS,
+            Auf::None | Auf::Pre => &U_TURNS[..1],
+        }
+    }
+
+    ///
+    /// Whether the algorithm spends moves on something the adjustments do for free, that is
+    /// turning the U layer before any other layer when pre-AUF is free, or after every other
+    /// layer when post-AUF is free
+    ///
+    /// Those algorithms are the same as shorter ones with a different adjustment
+    ///
+    pub fn wastes_moves(&self, solution: &Solution) -> bool {
+        let is_u_turn = |rot: &Rotation| rot.axis() == Axis::Y && rot.layers() == 0b100;
+        let starts_with_u = solution
+            .iter()
+            .take_while(|rot| rot.axis() == Axis::Y)
+            .any(is_u_turn);
+        let ends_with_u = solution
+            .iter()
+            .rev()
+            .take_while(|rot| rot.axis() == Axis::Y)
+            .any(is_u_turn);
+
+        (self.pre_turns().len() > 1 && starts_with_u)
+            || (self.post_turns().len() > 1 && ends_with_u)
+    }
+
+    ///
+    /// Returns the U turns to do before and after the algorithm for it to go from the initial
+    /// state to one of the desired states, preferring no turns
+    ///
+    pub fn adjustments(
+        &self,
+        initial_state: CubeState,
+        desired_states: &[CubePattern],
+        solution: &Solution,
+    ) -> Option<(Option<Rotation>, Option<Rotation>)> {
+        self.pre_turns()
+            .iter()
+            .flat_map(|&pre| self.post_turns().iter().map(move |&post| (pre, post)))
+            .find(|&(pre, post)| {
+                let state = pre.map_or(initial_state, |rot| initial_state.rotate(rot));
+                let state = solution.apply_to(state);
+                let state = post.map_or(state, |rot| state.rotate(rot));
+                desired_states.iter().any(|it| it.accepts(&state))
+            })
+    }
+}
+
+impl fmt::Display for Auf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let auf = match self {
+            Auf::None => "none",
+            Auf::Pre => "pre",
+            Auf::Post => "post",
+            Auf::Both => "both",
+        };
+        write!(f, "{}", auf)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Unknown AUF: {0}, expected none, pre, post or both")]
+pub struct UnknownAuf(String);
+
+impl FromStr for Auf {
+    type Err = UnknownAuf;
+
+    /// Case insensitive, e.g. `pre` or `BOTH`
+    fn from_str(auf: &str) -> Result<Auf, Self::Err> {
+        match auf.to_ascii_lowercase().as_str() {
+            "none" => Ok(Auf::None),
+            "pre" => Ok(Auf::Pre),
+            "post" => Ok(Auf::Post),
+            "both" => Ok(Auf::Both),
+            _ => Err(UnknownAuf(auf.to_string())),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+/*
+Coordinates of a cube: numbers describing just a part of it, like the orientation of every
+corner, so that tables indexed by them can tell how each move changes that part or how many
+moves at least solving it takes
+*/
+
+use crate::cubie::CubieCube;
+use crate::rotation::Rotation;
+
+pub(crate) const TWISTS: usize = 2187;
+pub(crate) const FLIPS: usize = 2048;
+/// Ways of placing the 4 E slice edges in the 12 edge positions
+pub(crate) const SLICES: usize = 495;
+pub(crate) const CORNER_PERMS: usize = 40320;
+/// Permutations of the 8 U and D layer edges
+pub(crate) const EDGE8_PERMS: usize = 40320;
+/// Permutations of the 4 E slice edges
+pub(crate) const SLICE_PERMS: usize = 24;
+
+/// Face turns, the same as the first rotations so they can be indexed by their discriminant
+pub(crate) const FACE_TURNS: [Rotation; 18] = [
+    Rotation::U,
+    Rotation::Up,
+    Rotation::U2,
+    Rotation::L,
+    Rotation::Lp,
+    Rotation::L2,
+    Rotation::F,
+    Rotation::Fp,
+    Rotation::F2,
+    Rotation::R,
+    Rotation::Rp,
+    Rotation::R2,
+    Rotation::B,
+    Rotation::Bp,
+    Rotation::B2,
+    Rotation::D,
+    Rotation::Dp,
+    Rotation::D2,
+];
+
+/// Ways of choosing k out of n
+const fn binomial(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    let mut result = 1;
+    let mut i = 0;
+    while i < k {
+        result = result * (n - i) / (i + 1);
+        i += 1;
+    }
+    result
+}
+
+pub(crate) fn twist(cube: &CubieCube) -> usize {
+    cube.co[..7]
+        .iter()
+        .fold(0, |twist, &co| twist * 3 + co as usize)
+}
+
+pub(crate) fn set_twist(cube: &mut CubieCube, mut twist: usize) {
+    let mut sum = 0;
+    for co in cube.co[..7].iter_mut().rev() {
+        *co = (twist % 3) as u8;
+        sum += *co;
+        twist /= 3;
+    }
+    cube.co[7] = (3 - sum % 3) % 3;
+}
+
+pub(crate) fn flip(cube: &CubieCube) -> usize {
+    cube.eo[..11]
+        .iter()
+        .fold(0, |flip, &eo| flip * 2 + eo as usize)
+}
+
+pub(crate) fn set_flip(cube: &mut CubieCube, mut flip: usize) {
+    let mut sum = 0;
+    for eo in cube.eo[..11].iter_mut().rev() {
+        *eo = (flip % 2) as u8;
+        sum += *eo;
+        flip /= 2;
+    }
+    cube.eo[11] = sum % 2;
+}
+
+/// Positions of the E slice edges (FR, FL, BL, BR), 0 when they are in the E slice
+pub(crate) fn slice(cube: &CubieCube) -> usize {
+    let mut slice = 0;
+    let mut found = 0;
+    for pos in (0..12).rev() {
+        if cube.ep[pos] >= 8 {
+            found += 1;
+            slice += binomial(11 - pos, found);
+        }
+    }
+    slice
+}
+
+pub(crate) fn set_slice(cube: &mut CubieCube, mut slice: usize) {
+    let mut is_slice = [false; 12];
+    let mut left = 4;
+    for (pos, is_slice) in is_slice.iter_mut().enumerate() {
+        if left > 0 && binomial(11 - pos, left) <= slice {
+            slice -= binomial(11 - pos, left);
+            *is_slice = true;
+            left -= 1;
+        }
+    }
+
+    let (mut slice_edge, mut other_edge) = (8, 0);
+    for (pos, &is_slice) in is_slice.iter().enumerate() {
+        let edge = if is_slice {
+            &mut slice_edge
+        } else {
+            &mut other_edge
+        };
+        cube.ep[pos] = *edge;
+        *edge += 1;
+    }
+}
+
+/// Index of a permutation in lexicographic order of all the permutations of its pieces
+pub(crate) fn permutation(pieces: &[u8]) -> usize {
+    (0..pieces.len()).fold(0, |index, i| {
+        let smaller_after = pieces[i + 1..].iter().filter(|&&it| it < pieces[i]).count();
+        index * (pieces.len() - i) + smaller_after
+    })
+}
+
+/// Places the pieces first..first + pieces.len() in the permutation with the given index
+pub(crate) fn set_permutation(pieces: &mut [u8], mut index: usize, first: u8) {
+    let n = pieces.len();
+    let mut smaller_after = vec![0; n];
+    for i in (0..n).rev() {
+        smaller_after[i] = index % (n - i);
+        index /= n - i;
+    }
+
+    let mut left: Vec<u8> = (first..first + n as u8).collect();
+    for (piece, smaller_after) in pieces.iter_mut().zip(smaller_after) {
+        *piece = left.remove(smaller_after);
+    }
+}
+
+/// Index of the positions of some pieces out of the given amount of slots, each one out of the
+/// slots the ones before left
+pub(crate) fn placement(positions: &[u8], slots: usize) -> usize {
+    (0..positions.len()).fold(0, |index, i| {
+        let taken = positions[..i]
+            .iter()
+            .filter(|&&it| it < positions[i])
+            .count();
+        index * (slots - i) + positions[i] as usize - taken
+    })
+}
+
+/// Positions of count pieces with the given placement index
+pub(crate) fn set_placement(mut index: usize, count: usize, slots: usize) -> Vec<u8> {
+    let mut free_idx = vec![0; count];
+    for i in (0..count).rev() {
+        free_idx[i] = index % (slots - i);
+        index /= slots - i;
+    }
+
+    let mut free: Vec<u8> = (0..slots as u8).collect();
+    free_idx.into_iter().map(|idx| free.remove(idx)).collect()
+}
+
+pub(crate) fn corner_perm(cube: &CubieCube) -> usize {
+    permutation(&cube.cp)
+}
+
+pub(crate) fn set_corner_perm(cube: &mut CubieCube, index: usize) {
+    set_permutation(&mut cube.cp, index, 0);
+}
+
+pub(crate) fn edge8_perm(cube: &CubieCube) -> usize {
+    permutation(&cube.ep[..8])
+}
+
+pub(crate) fn set_edge8_perm(cube: &mut CubieCube, index: usize) {
+    set_permutation(&mut cube.ep[..8], index, 0);
+}
+
+pub(crate) fn slice_perm(cube: &CubieCube) -> usize {
+    permutation(&cube.ep[8..])
+}
+
+pub(crate) fn set_slice_perm(cube: &mut CubieCube, index: usize) {
+    set_permutation(&mut cube.ep[8..], index, 8);
+}
+
+/// Coordinate reached by every move from every coordinate
+pub(crate) fn move_table<const M: usize>(
+    size: usize,
+    moves: &[Rotation; M],
+    set: fn(&mut CubieCube, usize),
+    get: fn(&CubieCube) -> usize,
+) -> Vec<[u16; M]> {
+    (0..size)
+        .map(|coord| {
+            let mut cube = CubieCube::SOLVED;
+            set(&mut cube, coord);
+            moves.map(|rot| get(&cube.rotate(rot)) as u16)
+        })
+        .collect()
+}
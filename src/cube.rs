@@ -14,7 +14,11 @@ red = 100
 blue = 101
 yellow = 110
 
-state = [cell; 50] (8 on each side ignoring the immobile centers + 2 extra)
+state = [cell; 56] (8 on each side + 2 extra + 6 centers)
+
+the centers only move with slice moves, wide moves and cube rotations, never with outer layer
+turns, so they are kept after the extra cells where the face masks of those turns never touch
+them
 
 
 colors of the cube:
@@ -29,15 +33,15 @@ cell indexes in state number:
 49: extra
 
           01 05 04
-          06    08
+          06 50 08
           02 07 03
 
 09 13 12  17 21 20  25 29 28  33 37 36
-14    16  22    24  30    32  38    40
+14 51 16  22 52 24  30 53 32  38 54 40
 10 15 11  18 23 19  26 31 27  34 39 35
 
           41 45 44
-          46    48
+          46 55 48
           42 47 43
 
 
@@ -88,16 +92,29 @@ whereas side moves (R R' L L') require a bit more and
 front moves (F F' B B') require the most.
 */
 
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::{fmt::Display, ops::Shl};
 
 use bnum::BUint;
 use thiserror::Error;
 
+use crate::algorithm::Algorithm;
+use crate::cubie::{self, CubieCube};
+use crate::geometry::{self, CELL_COUNT};
+use crate::parser::ParseErr;
 use crate::rotation::Rotation;
 
 const CELL_MASK: BUint<3> = BUint::<3>::parse_str_radix("111", 2);
 
+// lowest bit of each one of the 56 cells
+const CELL_LOW_BITS: BUint<3> =
+    BUint::<3>::parse_str_radix("249249249249249249249249249249249249249249", 16);
+
+// every stickers and center cell, without the extra ones
+const SPECIFIED_MASK: BUint<3> =
+    BUint::<3>::parse_str_radix("ffffc7fffffffffffffffffffffffffffffffffff8", 16);
+
 // -----------------------
 
 // 2**(3*1) + 2**(3*2) + 2**(3*3) + 2**(3*4) + 2**(3*5) + 2**(3*6) + 2**(3*7) + 2**(3*8)
@@ -358,10 +375,10 @@ const DOWN_SIDE_OVERFLOW_MASK: BUint<3> = CELL_MASK.mul(BUint::<3>::parse_str_ra
 const DOWN_SIDE_OVERFLOW_MASK_REV: BUint<3> =
     CELL_MASK.mul(BUint::<3>::parse_str_radix("200240", 16));
 
-const DISPLAYIDX_TO_CELLIDX: [usize; 54] = [
-    2, 7, 3, 6, 0, 8, 1, 5, 4, 10, 15, 11, 14, 0, 16, 9, 13, 12, 18, 23, 19, 22, 0, 24, 17, 21, 20,
-    26, 31, 27, 30, 0, 32, 25, 29, 28, 34, 39, 35, 38, 0, 40, 33, 37, 36, 42, 47, 43, 46, 0, 48,
-    41, 45, 44,
+pub(crate) const DISPLAYIDX_TO_CELLIDX: [usize; 54] = [
+    2, 7, 3, 6, 50, 8, 1, 5, 4, 10, 15, 11, 14, 51, 16, 9, 13, 12, 18, 23, 19, 22, 52, 24, 17, 21,
+    20, 26, 31, 27, 30, 53, 32, 25, 29, 28, 34, 39, 35, 38, 54, 40, 33, 37, 36, 42, 47, 43, 46, 55,
+    48, 41, 45, 44,
 ];
 
 const ONE: BUint<3> = BUint::ONE;
@@ -381,11 +398,17 @@ const ONE: BUint<3> = BUint::ONE;
 //     result
 // };
 
+const SOLVED_STR: &str = "WWWWWWWWWOOOOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBYYYYYYYYY";
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct CubeState {
     state: BUint<3>,
 }
 
+/// Cells specified in a state, with all the bits of each one of them set
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct CellMask(BUint<3>);
+
 impl Display for CubeState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.get_unwrapped_cube_str())
@@ -400,6 +423,29 @@ pub enum FromStrErr {
     InvalidChar(char),
 }
 
+/// Reasons a state can't be reached from the solved cube
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InvalidStateErr {
+    #[error("There are {1} {0} stickers, a cube has 9 of each color")]
+    ColorCount(char, usize),
+    #[error("The centers are not placed like the ones of a cube")]
+    Centers,
+    #[error("No corner has the colors of the one at {0}")]
+    Corner(&'static str),
+    #[error("No edge has the colors of the one at {0}")]
+    Edge(&'static str),
+    #[error("The {0} corner appears more than once")]
+    DuplicateCorner(&'static str),
+    #[error("The {0} edge appears more than once")]
+    DuplicateEdge(&'static str),
+    #[error("The corners are twisted, their orientation adds up to {0} (mod 3) instead of 0")]
+    Twist(u8),
+    #[error("An edge is flipped, their orientation adds up to 1 (mod 2) instead of 0")]
+    Flip,
+    #[error("Two pieces are swapped, corners and edges have a different permutation parity")]
+    Parity,
+}
+
 impl FromStr for CubeState {
     type Err = FromStrErr;
 
@@ -412,20 +458,7 @@ impl FromStr for CubeState {
             .iter()
             .zip(cube_str.chars())
             .try_fold(BUint::<3>::ZERO, |state, (&cellidx, color)| {
-                if cellidx == 0 {
-                    return Ok(state);
-                }
-
-                let cell: u8 = match color {
-                    'N' => 0b000,
-                    'W' => 0b001,
-                    'O' => 0b010,
-                    'G' => 0b011,
-                    'R' => 0b100,
-                    'B' => 0b101,
-                    'Y' => 0b110,
-                    _ => return Err(FromStrErr::InvalidChar(color)),
-                };
+                let cell = char_to_cell(color).ok_or(FromStrErr::InvalidChar(color))?;
 
                 Ok(state | (&ONE).shl(3 * cellidx).mul(cell.into()))
             })
@@ -433,26 +466,224 @@ impl FromStr for CubeState {
     }
 }
 
+pub(crate) fn char_to_cell(color: char) -> Option<u8> {
+    match color {
+        'N' => Some(0b000),
+        'W' => Some(0b001),
+        'O' => Some(0b010),
+        'G' => Some(0b011),
+        'R' => Some(0b100),
+        'B' => Some(0b101),
+        'Y' => Some(0b110),
+        _ => None,
+    }
+}
+
+/// # Panics
+/// Will panic if the cell has invalid bits
+pub(crate) fn cell_to_char(cell: u8) -> char {
+    match cell {
+        0b000 => 'N',
+        0b001 => 'W',
+        0b010 => 'O',
+        0b011 => 'G',
+        0b100 => 'R',
+        0b101 => 'B',
+        0b110 => 'Y',
+        _ => panic!("Invalid state"),
+    }
+}
+
+/// Cube rotations giving the 24 ways of holding a cube, starting with not rotating it
+pub(crate) fn orientation_rotations() -> impl Iterator<Item = [Option<Rotation>; 2]> {
+    let tops = [
+        None,
+        Some(Rotation::X),
+        Some(Rotation::X2),
+        Some(Rotation::Xp),
+    ];
+    let sides = [Some(Rotation::Z), Some(Rotation::Zp)];
+    let turns = [
+        None,
+        Some(Rotation::Y),
+        Some(Rotation::Y2),
+        Some(Rotation::Yp),
+    ];
+
+    tops.into_iter()
+        .chain(sides)
+        .flat_map(move |top| turns.into_iter().map(move |turn| [top, turn]))
+}
+
 impl CubeState {
+    /// Solved cube, white on top and green on front
+    pub fn solved() -> Self {
+        CubeState::from_str(SOLVED_STR).unwrap()
+    }
+
+    /// Returns the state reached by applying the scramble to the solved cube
+    pub fn from_scramble(scramble: &str) -> Result<Self, ParseErr> {
+        Ok(Algorithm::from_str(scramble)?.apply_to(CubeState::solved()))
+    }
+
+    ///
+    /// Checks that the state can be reached from the solved cube
+    ///
+    /// Unspecified stickers can be any color, so partially specified states are only checked
+    /// for impossible colors, while twist, flip and parity are checked on fully specified ones
+    ///
+    pub fn validate(&self) -> Result<(), InvalidStateErr> {
+        let stickers: Vec<u8> = (1..=48)
+            .chain(geometry::CENTER_START as u8..geometry::CENTER_START as u8 + 6)
+            .map(|idx| self.cell(idx))
+            .collect();
+        for color in 1..=6 {
+            let count = stickers.iter().filter(|&&it| it == color).count();
+            if count > 9 {
+                return Err(InvalidStateErr::ColorCount(cell_to_char(color), count));
+            }
+        }
+
+        let solved = CubeState::solved();
+        if !solved.orientations().any(|orientation| {
+            (geometry::CENTER_START as u8..geometry::CENTER_START as u8 + 6)
+                .all(|idx| self.cell(idx) == 0 || self.cell(idx) == orientation.cell(idx))
+        }) {
+            return Err(InvalidStateErr::Centers);
+        }
+
+        // a piece that can only be one corner or edge can't be anywhere else
+        let mut corners = [None; 8];
+        for (pos, name) in cubie::CORNER_NAMES.iter().enumerate() {
+            let pieces: HashSet<u8> = cubie::corner_candidates(self, pos)
+                .map(|(piece, _)| piece)
+                .collect();
+            match pieces.len() {
+                0 => return Err(InvalidStateErr::Corner(name)),
+                1 => {
+                    let piece = *pieces.iter().next().unwrap();
+                    if corners.contains(&Some(piece)) {
+                        return Err(InvalidStateErr::DuplicateCorner(
+                            cubie::CORNER_NAMES[piece as usize],
+                        ));
+                    }
+                    corners[pos] = Some(piece);
+                }
+                _ => {}
+            }
+        }
+
+        let mut edges = [None; 12];
+        for (pos, name) in cubie::EDGE_NAMES.iter().enumerate() {
+            let pieces: HashSet<u8> = cubie::edge_candidates(self, pos)
+                .map(|(piece, _)| piece)
+                .collect();
+            match pieces.len() {
+                0 => return Err(InvalidStateErr::Edge(name)),
+                1 => {
+                    let piece = *pieces.iter().next().unwrap();
+                    if edges.contains(&Some(piece)) {
+                        return Err(InvalidStateErr::DuplicateEdge(
+                            cubie::EDGE_NAMES[piece as usize],
+                        ));
+                    }
+                    edges[pos] = Some(piece);
+                }
+                _ => {}
+            }
+        }
+
+        let Ok(cubie) = CubieCube::try_from(*self) else {
+            // partially specified
+            return Ok(());
+        };
+        if cubie.twist() != 0 {
+            return Err(InvalidStateErr::Twist(cubie.twist()));
+        }
+        if cubie.flip() != 0 {
+            return Err(InvalidStateErr::Flip);
+        }
+        if cubie.corner_parity() != cubie.edge_parity() {
+            return Err(InvalidStateErr::Parity);
+        }
+
+        Ok(())
+    }
+
+    /// Returns every way of holding the solved cube whose centers could be the ones of this state,
+    /// for which the rest of the unspecified stickers can be filled in so that the cube can be solved
+    fn legal_center_placements(&self) -> Vec<CubeState> {
+        let mut cells = [0; CELL_COUNT];
+        for (idx, cell) in cells.iter_mut().enumerate() {
+            *cell = self.cell(idx as u8);
+        }
+        let centers = geometry::CENTER_START..geometry::CENTER_START + 6;
+
+        CubeState::solved()
+            .orientations()
+            .filter(|orientation| {
+                if !centers.clone().all(|idx| {
+                    let cell = self.cell(idx as u8);
+                    cell == 0 || cell == orientation.cell(idx as u8)
+                }) {
+                    return false;
+                }
+
+                for idx in centers.clone() {
+                    cells[idx] = orientation.cell(idx as u8);
+                }
+                let held = CubeState::from_cells(&cells)
+                    .oriented_like(&CubeState::solved())
+                    .unwrap();
+                cubie::has_legal_completion(&held)
+            })
+            .collect()
+    }
+
+    /// Whether the unspecified stickers can be filled in so that the cube can be solved
+    pub fn is_completable(&self) -> bool {
+        !self.legal_center_placements().is_empty()
+    }
+
+    ///
+    /// Whether some way of filling in the unspecified stickers of both states gives two cubes
+    /// connected by a sequence of moves
+    ///
+    /// Without center_moves (slices, wide moves or cube rotations) the centers can't move,
+    /// so both states need to have them in the same place
+    ///
+    pub fn can_reach(&self, desired: &CubeState, center_moves: bool) -> bool {
+        let initial = self.legal_center_placements();
+        let desired = desired.legal_center_placements();
+        if center_moves {
+            !initial.is_empty() && !desired.is_empty()
+        } else {
+            initial.iter().any(|centers| desired.contains(centers))
+        }
+    }
+
+    /// Builds a state from the color bits of every cell
+    pub(crate) fn from_cells(cells: &[u8; CELL_COUNT]) -> Self {
+        let state = cells
+            .iter()
+            .enumerate()
+            .fold(BUint::<3>::ZERO, |state, (idx, &cell)| {
+                state | BUint::<3>::from(cell).shl(3 * idx as u32)
+            });
+
+        CubeState { state }
+    }
+
     /// # Panics
     /// Will panic if idx is out of bounds
-    fn cell(&self, idx: u8) -> u8 {
+    pub(crate) fn cell(&self, idx: u8) -> u8 {
         (self.state).shr(3 * idx as u32).bitand(CELL_MASK).digits()[0] as u8
     }
 
     /// # Panics
     /// Will panic if idx is out of bounds or if the cell has invalid bits
     fn cell_char(&self, idx: u8) -> char {
-        match self.cell(idx) {
-            0b000 => 'N',
-            0b001 => 'W',
-            0b010 => 'O',
-            0b011 => 'G',
-            0b100 => 'R',
-            0b101 => 'B',
-            0b110 => 'Y',
-            _ => panic!("Invalid state"),
-        }
+        cell_to_char(self.cell(idx))
     }
 
     /// # Panics
@@ -465,7 +696,12 @@ impl CubeState {
                 self.cell_char(start + 4),
                 self.cell_char(start + 3)
             ),
-            1 => format!("{}   {}", self.cell_char(start), self.cell_char(start + 2)),
+            1 => format!(
+                "{} {} {}",
+                self.cell_char(start),
+                self.cell_char(geometry::CENTER_START as u8 + start / 8),
+                self.cell_char(start + 2)
+            ),
             2 => format!(
                 "{} {} {}",
                 self.cell_char(start),
@@ -574,6 +810,7 @@ impl CubeState {
                 side_cells ^= side_overflow_cells;
                 new.state ^= side_cells;
             }
+            Rotation::U2 => new = new.rotate(Rotation::U).rotate(Rotation::U),
             Rotation::L => {
                 let mut left_cells = new.state & LEFT_MASK;
                 new.state ^= left_cells;
@@ -622,6 +859,7 @@ impl CubeState {
                 new.state ^= side2_cells;
                 new.state ^= side3_cells;
             }
+            Rotation::L2 => new = new.rotate(Rotation::L).rotate(Rotation::L),
             Rotation::F => {
                 let mut front_cells = new.state & FRONT_MASK;
                 new.state ^= front_cells;
@@ -682,6 +920,7 @@ impl CubeState {
                 new.state ^= side3a_cells;
                 new.state ^= side3b_cells;
             }
+            Rotation::F2 => new = new.rotate(Rotation::F).rotate(Rotation::F),
             Rotation::R => {
                 let mut right_cells = new.state & RIGHT_MASK;
                 new.state ^= right_cells;
@@ -730,6 +969,7 @@ impl CubeState {
                 new.state ^= side2_cells;
                 new.state ^= side3_cells;
             }
+            Rotation::R2 => new = new.rotate(Rotation::R).rotate(Rotation::R),
             Rotation::B => {
                 let mut back_cells = new.state & BACK_MASK;
                 new.state ^= back_cells;
@@ -790,6 +1030,7 @@ impl CubeState {
                 new.state ^= side2_cells;
                 new.state ^= side3_cells;
             }
+            Rotation::B2 => new = new.rotate(Rotation::B).rotate(Rotation::B),
             Rotation::D => {
                 let mut down_cells = new.state & DOWN_MASK;
                 new.state ^= down_cells;
@@ -828,8 +1069,94 @@ impl CubeState {
                 side_cells ^= side_overflow_cells;
                 new.state ^= side_cells;
             }
+            Rotation::D2 => new = new.rotate(Rotation::D).rotate(Rotation::D),
+            _ => new = new.permute(&geometry::PERMUTATIONS[rotation as usize]),
         }
 
         new
     }
+
+    /// Turns the whole cube, changing how it is held without turning any layer
+    ///
+    /// # Panics
+    /// Will panic if the rotation is not one of x, y and z
+    pub fn reorient(self, rotation: Rotation) -> Self {
+        assert!(
+            rotation.is_cube_rotation(),
+            "{} is not a cube rotation",
+            rotation
+        );
+        self.rotate(rotation)
+    }
+
+    /// Returns the 24 ways of holding this cube, starting with the current one
+    pub fn orientations(self) -> impl Iterator<Item = CubeState> {
+        orientation_rotations().map(move |rotations| {
+            rotations
+                .into_iter()
+                .flatten()
+                .fold(self, |state, rot| state.reorient(rot))
+        })
+    }
+
+    /// Returns the orientation of this cube with the white center on top and the green one in
+    /// front, or the lowest orientation in the state ordering if there isn't one
+    pub fn canonical_orientation(self) -> Self {
+        self.orientations()
+            .find(|state| {
+                state.cell(geometry::CENTER_START as u8) == 0b001
+                    && state.cell(geometry::CENTER_START as u8 + 2) == 0b011
+            })
+            .unwrap_or_else(|| self.orientations().min_by_key(|state| state.state).unwrap())
+    }
+
+    /// Returns the orientation of this cube with the same centers as other, if there is one
+    pub fn oriented_like(self, other: &CubeState) -> Option<Self> {
+        self.orientations().find(|state| {
+            (geometry::CENTER_START..geometry::CENTER_START + 6)
+                .all(|idx| state.cell(idx as u8) == other.cell(idx as u8))
+        })
+    }
+
+    /// Whether both states are the same cube, just held differently
+    pub fn eq_up_to_rotation(&self, other: &CubeState) -> bool {
+        other.orientations().any(|state| state == *self)
+    }
+
+    /// Whether some stickers or centers are unspecified (N)
+    pub fn is_partial(&self) -> bool {
+        self.mask().0 != SPECIFIED_MASK
+    }
+
+    /// Whether this state is the pattern with its unspecified stickers filled in,
+    /// the unspecified stickers of this state only match unspecified ones
+    pub fn matches(&self, pattern: &CubeState) -> bool {
+        self.masked(&pattern.mask()) == *pattern
+    }
+
+    pub(crate) fn mask(&self) -> CellMask {
+        let state = self.state;
+        let specified = (state | state.shr(1) | state.shr(2)).bitand(CELL_LOW_BITS);
+        CellMask(specified | specified.shl(1) | specified.shl(2))
+    }
+
+    /// Returns the state with only the cells in the mask
+    pub(crate) fn masked(&self, mask: &CellMask) -> CubeState {
+        CubeState {
+            state: self.state.bitand(mask.0),
+        }
+    }
+
+    /// Moves every cell to the index given by the permutation,
+    /// much slower than the masks used for the face moves
+    pub(crate) fn permute(self, permutation: &[u8; CELL_COUNT]) -> Self {
+        let state = permutation
+            .iter()
+            .enumerate()
+            .fold(BUint::<3>::ZERO, |state, (idx, &dest)| {
+                state | BUint::<3>::from(self.cell(idx as u8)).shl(3 * dest as u32)
+            });
+
+        CubeState { state }
+    }
 }
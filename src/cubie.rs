@@ -0,0 +1,370 @@
+/*
+Cube described by its pieces instead of its stickers
+
+corners: URF, UFL, ULB, UBR, DFR, DLF, DBL, DRB
+edges: UR, UF, UL, UB, DR, DF, DL, DB, FR, FL, BL, BR
+
+cp[i] is the corner sitting at the position of the i-th corner, and co[i] how many times it is
+twisted clockwise, 0 when its U/D sticker is on the U/D face (same for the edges, flipped when
+the sticker that goes on the U/D face, or the F/B face for the middle layer ones, isn't there)
+
+centers[i] is the center sitting on the i-th face, in the order U L F R B D, so that slices,
+wide moves and cube rotations are just other permutations of the pieces. The solvers only care
+about the pieces relative to the centers, which normalized() gives by holding the cube with the
+centers back in place.
+*/
+
+use std::sync::LazyLock;
+
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+use crate::cube::{CubeState, InvalidStateErr};
+use crate::geometry::{self, CELL_COUNT};
+use crate::rotation::Rotation;
+
+pub const CORNER_NAMES: [&str; 8] = ["URF", "UFL", "ULB", "UBR", "DFR", "DLF", "DBL", "DRB"];
+
+pub const EDGE_NAMES: [&str; 12] = [
+    "UR", "UF", "UL", "UB", "DR", "DF", "DL", "DB", "FR", "FL", "BL", "BR",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CubieCube {
+    /// Corner permutation
+    pub cp: [u8; 8],
+    /// Corner orientation, from 0 to 2
+    pub co: [u8; 8],
+    /// Edge permutation
+    pub ep: [u8; 12],
+    /// Edge orientation, 0 or 1
+    pub eo: [u8; 12],
+    /// Center permutation
+    pub centers: [u8; 6],
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CubieErr {
+    #[error("The centers are unspecified or not placed like the ones of a cube")]
+    InvalidCenters,
+    #[error("Unspecified sticker at cell {0}")]
+    Unspecified(u8),
+    #[error("No corner has the colors of the one at position {0}")]
+    InvalidCorner(usize),
+    #[error("No edge has the colors of the one at position {0}")]
+    InvalidEdge(usize),
+    #[error("Corner {0} appears more than once")]
+    DuplicateCorner(u8),
+    #[error("Edge {0} appears more than once")]
+    DuplicateEdge(u8),
+}
+
+/// Why the two-phase and optimal solvers can't solve a state
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SolveErr {
+    #[error("The state has unspecified stickers")]
+    Partial,
+    #[error("Invalid state: {0}")]
+    Invalid(#[from] InvalidStateErr),
+    #[error("No solution of at most {0} moves")]
+    NotFound(u8),
+}
+
+/// Cubies of every rotation, indexed by the rotation discriminant
+static MOVES: LazyLock<Vec<CubieCube>> = LazyLock::new(|| {
+    Rotation::iter()
+        .map(|rot| CubieCube::try_from(CubeState::solved().rotate(rot)).unwrap())
+        .collect()
+});
+
+/// Cubies of the 24 ways of holding the solved cube
+static ORIENTATIONS: LazyLock<Vec<CubieCube>> = LazyLock::new(|| {
+    CubeState::solved()
+        .orientations()
+        .map(|state| CubieCube::try_from(state).unwrap())
+        .collect()
+});
+
+impl Default for CubieCube {
+    fn default() -> Self {
+        CubieCube::SOLVED
+    }
+}
+
+impl CubieCube {
+    pub const SOLVED: CubieCube = CubieCube {
+        cp: [0, 1, 2, 3, 4, 5, 6, 7],
+        co: [0; 8],
+        ep: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        eo: [0; 12],
+        centers: [0, 1, 2, 3, 4, 5],
+    };
+
+    /// Returns the cube reached by doing this one and then other
+    pub fn compose(&self, other: &CubieCube) -> CubieCube {
+        let mut result = CubieCube::SOLVED;
+        for i in 0..8 {
+            let from = other.cp[i] as usize;
+            result.cp[i] = self.cp[from];
+            result.co[i] = (self.co[from] + other.co[i]) % 3;
+        }
+        for i in 0..12 {
+            let from = other.ep[i] as usize;
+            result.ep[i] = self.ep[from];
+            result.eo[i] = (self.eo[from] + other.eo[i]) % 2;
+        }
+        for i in 0..6 {
+            result.centers[i] = self.centers[other.centers[i] as usize];
+        }
+        result
+    }
+
+    /// Returns the cube that composed with this one gives the solved cube
+    pub fn inverse(&self) -> CubieCube {
+        let mut result = CubieCube::SOLVED;
+        for i in 0..8 {
+            let piece = self.cp[i] as usize;
+            result.cp[piece] = i as u8;
+            result.co[piece] = (3 - self.co[i]) % 3;
+        }
+        for i in 0..12 {
+            let piece = self.ep[i] as usize;
+            result.ep[piece] = i as u8;
+            result.eo[piece] = self.eo[i];
+        }
+        for i in 0..6 {
+            result.centers[self.centers[i] as usize] = i as u8;
+        }
+        result
+    }
+
+    pub fn rotate(self, rotation: Rotation) -> Self {
+        self.compose(&MOVES[rotation as usize])
+    }
+
+    /// Returns the same cube held with every center in its place
+    pub fn normalized(&self) -> CubieCube {
+        let orientation = ORIENTATIONS
+            .iter()
+            .find(|orientation| orientation.centers == self.centers)
+            .expect("Centers should be placed like the ones of a cube");
+        self.compose(&orientation.inverse())
+    }
+
+    /// Returns the face turn of this cube as it is held doing what the given face turn does
+    /// to the normalized cube, which turns the face its center is on
+    pub fn turn_as_held(&self, rot: Rotation) -> Rotation {
+        let face = rot as usize / 3;
+        let pos = self
+            .centers
+            .iter()
+            .position(|&center| center as usize == face)
+            .expect("Every center should be on a face");
+        Rotation::from_repr(pos * 3 + rot as usize % 3).expect("Face turns come first")
+    }
+
+    /// Sum of the corner orientations modulo 3, 0 on any cube that can be solved
+    pub fn twist(&self) -> u8 {
+        self.normalized().co.iter().sum::<u8>() % 3
+    }
+
+    /// Sum of the edge orientations modulo 2, 0 on any cube that can be solved
+    pub fn flip(&self) -> u8 {
+        self.normalized().eo.iter().sum::<u8>() % 2
+    }
+
+    /// Whether the corners are an odd permutation, which on any cube that can be solved
+    /// is always the same as for the edges
+    pub fn corner_parity(&self) -> bool {
+        permutation_parity(&self.normalized().cp)
+    }
+
+    /// Whether the edges are an odd permutation
+    pub fn edge_parity(&self) -> bool {
+        permutation_parity(&self.normalized().ep)
+    }
+
+    /// Whether every piece is in its place relative to the centers
+    pub fn is_solved(&self) -> bool {
+        self.normalized() == CubieCube::SOLVED
+    }
+}
+
+fn permutation_parity(permutation: &[u8]) -> bool {
+    let mut odd = false;
+    for i in 0..permutation.len() {
+        for j in i + 1..permutation.len() {
+            if permutation[i] > permutation[j] {
+                odd = !odd;
+            }
+        }
+    }
+    odd
+}
+
+/// Returns every (piece, orientation) that can be at a position given its stickers,
+/// unspecified ones matching any color
+fn candidates<const N: usize>(
+    state: &CubeState,
+    cells: &'static [[u8; N]],
+    pos: usize,
+) -> impl Iterator<Item = (u8, u8)> {
+    let solved = CubeState::solved();
+    let colors = cells[pos].map(|cell| state.cell(cell));
+    (0..cells.len())
+        .flat_map(|piece| (0..N).map(move |ori| (piece, ori)))
+        .filter(move |&(piece, ori)| {
+            (0..N).all(|n| {
+                let color = colors[(n + ori) % N];
+                color == 0 || color == solved.cell(cells[piece][n])
+            })
+        })
+        .map(|(piece, ori)| (piece as u8, ori as u8))
+}
+
+/// Returns every (corner, orientation) that can be at the given corner position of a state
+pub(crate) fn corner_candidates(state: &CubeState, pos: usize) -> impl Iterator<Item = (u8, u8)> {
+    candidates(state, &geometry::CORNER_CELLS, pos)
+}
+
+/// Returns every (edge, orientation) that can be at the given edge position of a state
+pub(crate) fn edge_candidates(state: &CubeState, pos: usize) -> impl Iterator<Item = (u8, u8)> {
+    candidates(state, &geometry::EDGE_CELLS, pos)
+}
+
+/// Returns every (orientation sum, permutation parity) that can be reached by placing a different
+/// piece at every position out of its candidates, as a bitset indexed by sum * 2 + parity
+fn completions(candidates: &[Vec<(u8, u8)>], modulus: u8) -> u8 {
+    // pieces are placed position by position, so the mask of used pieces tells the position
+    let mut reachable = vec![0u8; 1 << candidates.len()];
+    reachable[0] = 1;
+    for mask in 0..reachable.len() {
+        let pos = mask.count_ones() as usize;
+        if pos == candidates.len() || reachable[mask] == 0 {
+            continue;
+        }
+
+        let current = reachable[mask];
+        for &(piece, ori) in &candidates[pos] {
+            if mask & (1 << piece) != 0 {
+                continue;
+            }
+            // pieces already placed that are greater than this one are inversions
+            let inversions = (mask >> (piece + 1)).count_ones() as u8 & 1;
+            for bit in (0..2 * modulus).filter(|bit| current & (1 << bit) != 0) {
+                let sum = (bit / 2 + ori) % modulus;
+                let parity = (bit % 2) ^ inversions;
+                reachable[mask | (1 << piece)] |= 1 << (sum * 2 + parity);
+            }
+        }
+    }
+
+    reachable[reachable.len() - 1]
+}
+
+/// Whether the unspecified stickers of a state held with its centers in place can be filled in
+/// so that the cube can be solved
+pub(crate) fn has_legal_completion(state: &CubeState) -> bool {
+    let corners: Vec<Vec<(u8, u8)>> = (0..8)
+        .map(|pos| corner_candidates(state, pos).collect())
+        .collect();
+    let edges: Vec<Vec<(u8, u8)>> = (0..12)
+        .map(|pos| edge_candidates(state, pos).collect())
+        .collect();
+
+    // no twist and no flip, with the same parity on both
+    let corners = completions(&corners, 3);
+    let edges = completions(&edges, 2);
+    (0..2).any(|parity| corners & edges & (1 << parity) != 0)
+}
+
+impl CubieCube {
+    /// Reads the pieces of a state the solvers can solve: fully specified and reachable
+    pub fn solvable(state: CubeState) -> Result<Self, SolveErr> {
+        if state.is_partial() {
+            return Err(SolveErr::Partial);
+        }
+        state.validate()?;
+
+        Ok(CubieCube::try_from(state).expect("A valid state should have valid pieces"))
+    }
+}
+
+impl TryFrom<CubeState> for CubieCube {
+    type Error = CubieErr;
+
+    /// Reads the pieces of a fully specified state
+    fn try_from(state: CubeState) -> Result<Self, Self::Error> {
+        let solved = CubeState::solved();
+        if !solved.orientations().any(|orientation| {
+            (geometry::CENTER_START..geometry::CENTER_START + 6)
+                .all(|cell| orientation.cell(cell as u8) == state.cell(cell as u8))
+        }) {
+            return Err(CubieErr::InvalidCenters);
+        }
+
+        if let Some(cell) = (1..=48).find(|&cell| state.cell(cell) == 0) {
+            return Err(CubieErr::Unspecified(cell));
+        }
+
+        let mut result = CubieCube::SOLVED;
+        for (pos, center) in result.centers.iter_mut().enumerate() {
+            let color = state.cell((geometry::CENTER_START + pos) as u8);
+            *center = (0..6)
+                .find(|&face| solved.cell((geometry::CENTER_START + face) as u8) == color)
+                .unwrap() as u8;
+        }
+
+        for pos in 0..8 {
+            (result.cp[pos], result.co[pos]) = corner_candidates(&state, pos)
+                .next()
+                .ok_or(CubieErr::InvalidCorner(pos))?;
+        }
+
+        for pos in 0..12 {
+            (result.ep[pos], result.eo[pos]) = edge_candidates(&state, pos)
+                .next()
+                .ok_or(CubieErr::InvalidEdge(pos))?;
+        }
+
+        for piece in 0..8 {
+            if result.cp.iter().filter(|&&it| it == piece).count() > 1 {
+                return Err(CubieErr::DuplicateCorner(piece));
+            }
+        }
+        for piece in 0..12 {
+            if result.ep.iter().filter(|&&it| it == piece).count() > 1 {
+                return Err(CubieErr::DuplicateEdge(piece));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl From<CubieCube> for CubeState {
+    fn from(cubie: CubieCube) -> Self {
+        let solved = CubeState::solved();
+        let mut cells = [0; CELL_COUNT];
+        for (pos, &center) in cubie.centers.iter().enumerate() {
+            cells[geometry::CENTER_START + pos] =
+                solved.cell((geometry::CENTER_START + center as usize) as u8);
+        }
+
+        for (pos, cells_at) in geometry::CORNER_CELLS.iter().enumerate() {
+            let piece = &geometry::CORNER_CELLS[cubie.cp[pos] as usize];
+            for n in 0..3 {
+                cells[cells_at[(n + cubie.co[pos] as usize) % 3] as usize] = solved.cell(piece[n]);
+            }
+        }
+
+        for (pos, cells_at) in geometry::EDGE_CELLS.iter().enumerate() {
+            let piece = &geometry::EDGE_CELLS[cubie.ep[pos] as usize];
+            for n in 0..2 {
+                cells[cells_at[(n + cubie.eo[pos] as usize) % 2] as usize] = solved.cell(piece[n]);
+            }
+        }
+
+        CubeState::from_cells(&cells)
+    }
+}
@@ -7,3 +7,11 @@ pub enum Face {
     B,
     D,
 }
+
+/// Axes of the cube, each one pointing from the L, D and B faces to the R, U and F faces
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
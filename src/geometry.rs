@@ -0,0 +1,233 @@
+/*
+Spatial layout of the cells described in cube.rs
+
+Every cell is mapped to the position of the cubie it belongs to and to the direction
+its sticker is facing, both as vectors with coordinates in {-1, 0, 1}:
+
+x: L (-1) -> R (1)
+y: D (-1) -> U (1)
+z: B (-1) -> F (1)
+
+Turning a set of layers around an axis is then just rotating those vectors, which lets us
+generate the cell permutation of any move (slices, wide moves, whole cube rotations) at
+compile time instead of writing masks by hand for each one of them.
+*/
+
+use strum::EnumCount;
+
+use crate::rotation::Rotation;
+
+/// Amount of cells in the state, including the extra ones and the centers
+pub const CELL_COUNT: usize = 56;
+
+/// First cell index of each face, in the order U L F R B D
+const FACE_STARTS: [usize; 6] = [1, 9, 17, 25, 33, 41];
+
+/// First center cell index, centers follow the same face order as FACE_STARTS
+pub const CENTER_START: usize = 50;
+
+/// (normal, column direction, row direction) of each face as seen in the unwrapped cube
+const FACE_FRAMES: [[[i8; 3]; 3]; 6] = [
+    [[0, 1, 0], [1, 0, 0], [0, 0, 1]],
+    [[-1, 0, 0], [0, 0, 1], [0, -1, 0]],
+    [[0, 0, 1], [1, 0, 0], [0, -1, 0]],
+    [[1, 0, 0], [0, 0, -1], [0, -1, 0]],
+    [[0, 0, -1], [-1, 0, 0], [0, -1, 0]],
+    [[0, -1, 0], [1, 0, 0], [0, 0, -1]],
+];
+
+/// (row, column) of each cell of a face relative to its start
+const FACE_OFFSETS: [(i8, i8); 8] = [
+    (0, 0),
+    (2, 0),
+    (2, 2),
+    (0, 2),
+    (0, 1),
+    (1, 0),
+    (2, 1),
+    (1, 2),
+];
+
+/// Returns the (face, row, column) of a cell, or None for the extra cells
+const fn cell_face_coords(cell: usize) -> Option<(usize, i8, i8)> {
+    if cell >= CENTER_START && cell < CENTER_START + 6 {
+        return Some((cell - CENTER_START, 1, 1));
+    }
+
+    if cell == 0 || cell > 48 {
+        return None;
+    }
+
+    let face = (cell - 1) / 8;
+    let (row, col) = FACE_OFFSETS[cell - FACE_STARTS[face]];
+    Some((face, row, col))
+}
+
+/// Returns the (position, normal) vectors of a cell, or None for the extra cells
+const fn cell_vectors(cell: usize) -> Option<([i8; 3], [i8; 3])> {
+    let (face, row, col) = match cell_face_coords(cell) {
+        Some(coords) => coords,
+        None => return None,
+    };
+
+    let [normal, col_dir, row_dir] = FACE_FRAMES[face];
+    let mut pos = [0; 3];
+    let mut i = 0;
+    while i < 3 {
+        pos[i] = normal[i] + (col - 1) * col_dir[i] + (row - 1) * row_dir[i];
+        i += 1;
+    }
+
+    Some((pos, normal))
+}
+
+/// # Panics
+/// Will panic if no cell has the given position and normal
+const fn find_cell(pos: [i8; 3], normal: [i8; 3]) -> usize {
+    let mut cell = 0;
+    while cell < CELL_COUNT {
+        if let Some((p, n)) = cell_vectors(cell) {
+            if p[0] == pos[0]
+                && p[1] == pos[1]
+                && p[2] == pos[2]
+                && n[0] == normal[0]
+                && n[1] == normal[1]
+                && n[2] == normal[2]
+            {
+                return cell;
+            }
+        }
+        cell += 1;
+    }
+
+    panic!("No cell at the given position");
+}
+
+/// Rotates a vector a quarter turn clockwise, looking from the positive side of the axis
+const fn rotate_vector(v: [i8; 3], axis: usize) -> [i8; 3] {
+    match axis {
+        0 => [v[0], v[2], -v[1]],
+        1 => [-v[2], v[1], v[0]],
+        2 => [v[1], -v[0], v[2]],
+        _ => panic!("Invalid axis"),
+    }
+}
+
+/// Returns the destination cell of every cell after the given rotation
+pub const fn permutation(rotation: Rotation) -> [u8; CELL_COUNT] {
+    let axis = rotation.axis() as usize;
+    let layers = rotation.layers();
+    let turns = rotation.turns().rem_euclid(4);
+
+    let mut result = [0; CELL_COUNT];
+    let mut cell = 0;
+    while cell < CELL_COUNT {
+        result[cell] = cell as u8;
+
+        if let Some((mut pos, mut normal)) = cell_vectors(cell) {
+            let layer = (pos[axis] + 1) as u8;
+            if layers & (1 << layer) != 0 {
+                let mut i = 0;
+                while i < turns {
+                    pos = rotate_vector(pos, axis);
+                    normal = rotate_vector(normal, axis);
+                    i += 1;
+                }
+                result[cell] = find_cell(pos, normal) as u8;
+            }
+        }
+
+        cell += 1;
+    }
+
+    result
+}
+
+/// Cell permutations of every rotation, indexed by the rotation discriminant
+pub const PERMUTATIONS: [[u8; CELL_COUNT]; Rotation::COUNT] = {
+    let mut result = [[0; CELL_COUNT]; Rotation::COUNT];
+    let mut idx = 0;
+    while idx < Rotation::COUNT {
+        result[idx] = match Rotation::from_repr(idx) {
+            Some(rotation) => permutation(rotation),
+            None => panic!("Invalid rotation index"),
+        };
+        idx += 1;
+    }
+    result
+};
+
+/// Faces of the corners in the usual order URF, UFL, ULB, UBR, DFR, DLF, DBL, DRB,
+/// each one starting with its U/D face and going clockwise
+const CORNER_FACES: [[usize; 3]; 8] = [
+    [0, 3, 2],
+    [0, 2, 1],
+    [0, 1, 4],
+    [0, 4, 3],
+    [5, 2, 3],
+    [5, 1, 2],
+    [5, 4, 1],
+    [5, 3, 4],
+];
+
+/// Faces of the edges in the usual order UR, UF, UL, UB, DR, DF, DL, DB, FR, FL, BL, BR,
+/// each one starting with its U/D face, or its F/B face for the middle layer edges
+const EDGE_FACES: [[usize; 2]; 12] = [
+    [0, 3],
+    [0, 2],
+    [0, 1],
+    [0, 4],
+    [5, 3],
+    [5, 2],
+    [5, 1],
+    [5, 4],
+    [2, 3],
+    [2, 1],
+    [4, 1],
+    [4, 3],
+];
+
+/// Returns the cell of every face of a piece, given as its faces in the order U L F R B D
+const fn piece_cells<const N: usize>(faces: [usize; N]) -> [u8; N] {
+    let mut pos = [0; 3];
+    let mut i = 0;
+    while i < N {
+        let normal = FACE_FRAMES[faces[i]][0];
+        let mut axis = 0;
+        while axis < 3 {
+            pos[axis] += normal[axis];
+            axis += 1;
+        }
+        i += 1;
+    }
+
+    let mut result = [0; N];
+    let mut i = 0;
+    while i < N {
+        result[i] = find_cell(pos, FACE_FRAMES[faces[i]][0]) as u8;
+        i += 1;
+    }
+    result
+}
+
+/// Cells of every corner, in the order of CORNER_FACES
+pub const CORNER_CELLS: [[u8; 3]; 8] = {
+    let mut result = [[0; 3]; 8];
+    let mut i = 0;
+    while i < 8 {
+        result[i] = piece_cells(CORNER_FACES[i]);
+        i += 1;
+    }
+    result
+};
+
+/// Cells of every edge, in the order of EDGE_FACES
+pub const EDGE_CELLS: [[u8; 2]; 12] = {
+    let mut result = [[0; 2]; 12];
+    let mut i = 0;
+    while i < 12 {
+        result[i] = piece_cells(EDGE_FACES[i]);
+        i += 1;
+    }
+    result
+};
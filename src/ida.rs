@@ -0,0 +1,569 @@
+/*
+Iterative-deepening A* generator, finding every algorithm of a given length in almost no memory
+
+It goes through the paths from the initial state depth first like the bidirectional solver, but
+without keeping the states halfway, and leaves a path as soon as a lower bound of what reaching
+a desired state costs is more than what the path has left. The bounds come from pattern
+databases of small parts of the cube: 4 corners, 4 edges, or the orientation of every piece.
+Each one holds, for every way of placing its part, the cost of reaching a placement compatible
+with a desired state, which only depends on the stickers the desired states specify, so the
+bounds hold for partial desired states and sets of colors as well.
+
+The databases are built for the desired states, moves and metric of each search, which takes
+about a second. They only take about 5 MB, but the tables of where each move takes each index
+they are built from take 4 bytes per index and move, about 60 MB with the 18 face turns and
+150 MB with the slice and wide moves as well.
+*/
+
+use std::sync::mpsc;
+use std::thread;
+
+use crate::coord::*;
+use crate::cube::CubeState;
+use crate::cubie::CubieCube;
+use crate::geometry::{CORNER_CELLS, EDGE_CELLS};
+use crate::metric::Metric;
+use crate::pattern::CubePattern;
+use crate::rotation::Rotation;
+use crate::solution::{self, Solution};
+use crate::solver::Stop;
+
+/// Pieces whose position and orientation the corner and edge databases track
+const TRACKED: usize = 4;
+
+/// Cost of the placements no move sequence reaches a compatible placement from
+const UNREACHABLE: u8 = u8::MAX;
+
+/// Coordinate of the part of the cube a pattern database keeps track of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Coord {
+    /// Positions and orientations of the 4 corners starting at the given one
+    Corners(u8),
+    /// Positions and orientations of the 4 edges starting at the given one
+    Edges(u8),
+    Twist,
+    Flip,
+}
+
+const COORDS: [Coord; 7] = [
+    Coord::Corners(0),
+    Coord::Corners(4),
+    Coord::Edges(0),
+    Coord::Edges(4),
+    Coord::Edges(8),
+    Coord::Twist,
+    Coord::Flip,
+];
+
+/// Coordinates each database is indexed by, out of COORDS, a database of two of them being
+/// indexed by first * size of the second + second
+const DATABASES: [(usize, Option<usize>); 6] = [
+    (0, None),
+    (1, None),
+    (2, None),
+    (3, None),
+    (4, None),
+    (5, Some(6)),
+];
+
+/// Positions of the pieces first..first + 4 out of every position of perm, then their
+/// orientations out of oris
+fn tracked(perm: &[u8], ori: &[u8], first: u8, oris: usize) -> usize {
+    let mut positions = [0; TRACKED];
+    let mut orientations = 0;
+    for (piece, position) in positions.iter_mut().enumerate() {
+        let pos = perm
+            .iter()
+            .position(|&it| it == first + piece as u8)
+            .unwrap();
+        *position = pos as u8;
+        orientations = orientations * oris + ori[pos] as usize;
+    }
+    placement(&positions, perm.len()) * oris.pow(TRACKED as u32) + orientations
+}
+
+/// Positions and orientations of the tracked pieces with the given index
+fn set_tracked(index: usize, slots: usize, oris: usize) -> (Vec<u8>, [u8; TRACKED]) {
+    let combinations = oris.pow(TRACKED as u32);
+    let positions = set_placement(index / combinations, TRACKED, slots);
+    let mut orientations = [0; TRACKED];
+    let mut left = index % combinations;
+    for ori in orientations.iter_mut().rev() {
+        *ori = (left % oris) as u8;
+        left /= oris;
+    }
+    (positions, orientations)
+}
+
+/// Places the tracked pieces, and the other ones anywhere else
+fn place_tracked(perm: &mut [u8], ori: &mut [u8], first: u8, oris: usize, index: usize) {
+    let (positions, orientations) = set_tracked(index, perm.len(), oris);
+    let mut others = (0..perm.len() as u8).filter(|piece| !(first..first + 4).contains(piece));
+    for pos in 0..perm.len() {
+        (perm[pos], ori[pos]) = match positions.iter().position(|&it| it as usize == pos) {
+            Some(piece) => (first + piece as u8, orientations[piece]),
+            None => (others.next().unwrap(), 0),
+        };
+    }
+}
+
+/// Keeps the candidates of the tracked pieces where they are, and the other pieces elsewhere
+fn restrict_tracked(candidates: &mut [u32], first: u8, oris: usize, index: usize) {
+    let (positions, orientations) = set_tracked(index, candidates.len(), oris);
+    let tracked_pieces: u32 = (0..TRACKED)
+        .map(|piece| ((1 << oris) - 1) << ((first as usize + piece) * oris))
+        .sum();
+    for (pos, candidates) in candidates.iter_mut().enumerate() {
+        *candidates &= match positions.iter().position(|&it| it as usize == pos) {
+            Some(piece) => 1 << ((first as usize + piece) * oris + orientations[piece] as usize),
+            None => !tracked_pieces,
+        };
+    }
+}
+
+/// Candidates of every piece with the given orientation
+fn with_orientation(pieces: usize, oris: usize, ori: u8) -> u32 {
+    (0..pieces)
+        .map(|piece| 1 << (piece * oris + ori as usize))
+        .sum()
+}
+
+impl Coord {
+    fn size(self) -> usize {
+        match self {
+            Coord::Corners(_) => 8 * 7 * 6 * 5 * 3usize.pow(TRACKED as u32),
+            Coord::Edges(_) => 12 * 11 * 10 * 9 * 2usize.pow(TRACKED as u32),
+            Coord::Twist => TWISTS,
+            Coord::Flip => FLIPS,
+        }
+    }
+
+    fn index(self, cube: &CubieCube) -> usize {
+        match self {
+            Coord::Corners(first) => tracked(&cube.cp, &cube.co, first, 3),
+            Coord::Edges(first) => tracked(&cube.ep, &cube.eo, first, 2),
+            Coord::Twist => twist(cube),
+            Coord::Flip => flip(cube),
+        }
+    }
+
+    /// Some cube with the given index
+    fn cube(self, index: usize) -> CubieCube {
+        let mut cube = CubieCube::SOLVED;
+        match self {
+            Coord::Corners(first) => place_tracked(&mut cube.cp, &mut cube.co, first, 3, index),
+            Coord::Edges(first) => place_tracked(&mut cube.ep, &mut cube.eo, first, 2, index),
+            Coord::Twist => set_twist(&mut cube, index),
+            Coord::Flip => set_flip(&mut cube, index),
+        }
+        cube
+    }
+
+    /// Index reached by each move from each index, indexed by index * moves.len() + move, or
+    /// None when the search is stopped before it's done
+    fn move_table(self, moves: &[Rotation], stop: &Stop) -> Option<Vec<u32>> {
+        // which pieces are tracked doesn't change where the moves take them
+        let (slots, oris) = match self {
+            Coord::Corners(_) => (8, 3),
+            Coord::Edges(_) => (12, 2),
+            Coord::Twist | Coord::Flip => {
+                return Some(
+                    (0..self.size())
+                        .flat_map(|index| {
+                            let cube = self.cube(index);
+                            moves
+                                .iter()
+                                .map(move |&rot| self.index(&cube.rotate(rot)) as u32)
+                        })
+                        .collect(),
+                );
+            }
+        };
+
+        // the slot each slot goes to with each move, and how much its piece gets twisted
+        let slot_moves: Vec<Vec<(u8, u8)>> = moves
+            .iter()
+            .map(|&rot| {
+                let cube = CubieCube::SOLVED.rotate(rot);
+                let (perm, ori) = match self {
+                    Coord::Corners(_) => (&cube.cp[..], &cube.co[..]),
+                    _ => (&cube.ep[..], &cube.eo[..]),
+                };
+                (0..slots as u8)
+                    .map(|slot| {
+                        let dest = perm.iter().position(|&it| it == slot).unwrap();
+                        (dest as u8, ori[dest])
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut table = Vec::with_capacity(self.size() * moves.len());
+        for index in 0..self.size() {
+            // the table takes a while to fill, so the search can stop in between
+            if index % 4096 == 0 && stop.is_set() {
+                return None;
+            }
+
+            let (positions, orientations) = set_tracked(index, slots, oris);
+            table.extend(slot_moves.iter().map(|slot_moves| {
+                let mut new_positions = [0; TRACKED];
+                let mut new_orientations = 0;
+                for piece in 0..TRACKED {
+                    let (dest, twist) = slot_moves[positions[piece] as usize];
+                    new_positions[piece] = dest;
+                    new_orientations =
+                        new_orientations * oris + (orientations[piece] + twist) as usize % oris;
+                }
+                (placement(&new_positions, slots) * oris.pow(TRACKED as u32) + new_orientations)
+                    as u32
+            }));
+        }
+        Some(table)
+    }
+
+    ///
+    /// Narrows down the pieces that can be at each position of a cube with the given index, as
+    /// bits piece * 3 + orientation for the corners and piece * 2 + orientation for the edges
+    ///
+    fn restrict(self, index: usize, corners: &mut [u32; 8], edges: &mut [u32; 12]) {
+        match self {
+            Coord::Corners(first) => restrict_tracked(corners, first, 3, index),
+            Coord::Edges(first) => restrict_tracked(edges, first, 2, index),
+            Coord::Twist => {
+                for (candidates, &co) in corners.iter_mut().zip(&self.cube(index).co) {
+                    *candidates &= with_orientation(8, 3, co);
+                }
+            }
+            Coord::Flip => {
+                for (candidates, &eo) in edges.iter_mut().zip(&self.cube(index).eo) {
+                    *candidates &= with_orientation(12, 2, eo);
+                }
+            }
+        }
+    }
+}
+
+/// Pieces each position of a desired state accepts, as bits like the candidates of Coord::restrict
+struct Accepted {
+    corners: [u32; 8],
+    edges: [u32; 12],
+}
+
+impl Accepted {
+    fn new(pattern: &CubePattern) -> Self {
+        fn accepted<const N: usize>(pattern: &CubePattern, cells: &[[u8; N]], pos: usize) -> u32 {
+            let solved = CubeState::solved();
+            let mut accepted = 0;
+            for piece in 0..cells.len() {
+                for ori in 0..N {
+                    let fits = (0..N).all(|n| {
+                        let cell = cells[pos][(n + ori) % N] as usize;
+                        pattern.cell_colors(cell) & 1 << solved.cell(cells[piece][n]) != 0
+                    });
+                    if fits {
+                        accepted |= 1 << (piece * N + ori);
+                    }
+                }
+            }
+            accepted
+        }
+
+        Accepted {
+            corners: std::array::from_fn(|pos| accepted(pattern, &CORNER_CELLS, pos)),
+            edges: std::array::from_fn(|pos| accepted(pattern, &EDGE_CELLS, pos)),
+        }
+    }
+
+    fn fits(&self, corners: &[u32; 8], edges: &[u32; 12]) -> bool {
+        corners.iter().zip(&self.corners).all(|(a, b)| a & b != 0)
+            && edges.iter().zip(&self.edges).all(|(a, b)| a & b != 0)
+    }
+}
+
+/// Pattern databases of the desired states of a search, for its moves and metric
+pub(crate) struct Ida {
+    moves: Vec<Rotation>,
+    metric: Metric,
+    goals: Vec<CubePattern>,
+    /// Coordinate reached by each move from each one, indexed by coord * moves.len() + move,
+    /// for each one of COORDS
+    move_tables: Vec<Vec<u32>>,
+    /// Least cost of reaching a desired state from each index of each one of DATABASES
+    databases: Vec<Vec<u8>>,
+}
+
+impl Ida {
+    /// Whether the databases are the ones of these desired states, moves and metric
+    pub(crate) fn is_for(&self, goals: &[CubePattern], moves: &[Rotation], metric: Metric) -> bool {
+        self.goals == goals && self.moves == moves && self.metric == metric
+    }
+
+    /// Builds the databases, or returns None when the search is stopped before they are done
+    pub(crate) fn new(
+        goals: &[CubePattern],
+        moves: &[Rotation],
+        metric: Metric,
+        stop: &Stop,
+    ) -> Option<Ida> {
+        let move_tables = COORDS
+            .iter()
+            .map(|coord| coord.move_table(moves, stop))
+            .collect::<Option<_>>()?;
+
+        let mut ida = Ida {
+            moves: moves.to_vec(),
+            metric,
+            goals: goals.to_vec(),
+            move_tables,
+            databases: Vec::new(),
+        };
+        let accepted: Vec<Accepted> = goals.iter().map(Accepted::new).collect();
+        ida.databases = DATABASES
+            .iter()
+            .map(|&coords| ida.database(coords, &accepted, stop))
+            .collect::<Option<_>>()?;
+        Some(ida)
+    }
+
+    fn next(&self, coord: usize, index: u32, m: usize) -> u32 {
+        self.move_tables[coord][index as usize * self.moves.len() + m]
+    }
+
+    /// Size of the second coordinate of a database, 1 when there is none
+    fn second_size(second: Option<usize>) -> usize {
+        second.map_or(1, |coord| COORDS[coord].size())
+    }
+
+    /// Pieces that can be at each position for every index of a coordinate
+    fn restrictions(coord: Option<usize>) -> Vec<([u32; 8], [u32; 12])> {
+        let Some(coord) = coord else {
+            return vec![([u32::MAX; 8], [u32::MAX; 12])];
+        };
+        (0..COORDS[coord].size())
+            .map(|index| {
+                let (mut corners, mut edges) = ([u32::MAX; 8], [u32::MAX; 12]);
+                COORDS[coord].restrict(index, &mut corners, &mut edges);
+                (corners, edges)
+            })
+            .collect()
+    }
+
+    ///
+    /// Least cost of reaching a placement some desired state accepts from each placement of the
+    /// coordinates, going by increasing cost from those placements
+    ///
+    /// Every move is undone by another one of the same cost, so the cost of reaching one from
+    /// the other is the same both ways
+    ///
+    fn database(
+        &self,
+        (first, second): (usize, Option<usize>),
+        accepted: &[Accepted],
+        stop: &Stop,
+    ) -> Option<Vec<u8>> {
+        let second_size = Self::second_size(second);
+        let size = COORDS[first].size() * second_size;
+
+        let (first_restrictions, second_restrictions) =
+            (Self::restrictions(Some(first)), Self::restrictions(second));
+        let mut costs: Vec<u8> = (0..size)
+            .map(|index| {
+                let (a, b) = (
+                    &first_restrictions[index / second_size],
+                    &second_restrictions[index % second_size],
+                );
+                let corners = std::array::from_fn(|pos| a.0[pos] & b.0[pos]);
+                let edges = std::array::from_fn(|pos| a.1[pos] & b.1[pos]);
+                if accepted.iter().any(|it| it.fits(&corners, &edges)) {
+                    0
+                } else {
+                    UNREACHABLE
+                }
+            })
+            .collect();
+
+        let move_costs: Vec<u8> = self
+            .moves
+            .iter()
+            .map(|&rot| self.metric.rotation_cost(rot) as u8)
+            .collect();
+        let mut max_cost = 0;
+        let mut cost = 0;
+        while cost <= max_cost {
+            if stop.is_set() {
+                return None;
+            }
+            for index in 0..size {
+                if costs[index] != cost {
+                    continue;
+                }
+                let (a, b) = (index / second_size, index % second_size);
+                for (m, &move_cost) in move_costs.iter().enumerate() {
+                    let next_a = self.next(first, a as u32, m) as usize;
+                    let next_b = second.map_or(0, |coord| self.next(coord, b as u32, m) as usize);
+                    let next = next_a * second_size + next_b;
+                    let next_cost = cost + move_cost;
+                    if next_cost < costs[next] {
+                        costs[next] = next_cost;
+                        max_cost = max_cost.max(next_cost);
+                    }
+                }
+            }
+            cost += 1;
+        }
+        Some(costs)
+    }
+
+    /// Least cost of reaching a desired state according to the databases
+    fn lower_bound(&self, indexes: &[u32; COORDS.len()]) -> u8 {
+        DATABASES
+            .iter()
+            .zip(&self.databases)
+            .map(|(&(first, second), costs)| {
+                let second_index = second.map_or(0, |coord| indexes[coord] as usize);
+                costs[indexes[first] as usize * Self::second_size(second) + second_index]
+            })
+            .max()
+            .unwrap()
+    }
+
+    ///
+    /// Finds every algorithm costing exactly move_count going from the initial state to a
+    /// desired state, and passes each one to found as soon as it's found
+    ///
+    /// The databases only know the pieces of fully specified valid states, so partial or invalid
+    /// initial states are searched without them, through every path
+    ///
+    pub(crate) fn run(
+        &self,
+        initial_state: CubeState,
+        move_count: u8,
+        multi_threaded: bool,
+        stop: &Stop,
+        found: &mut dyn FnMut(Solution),
+    ) {
+        let indexes =
+            (!initial_state.is_partial() && initial_state.validate().is_ok()).then(|| {
+                let cube = CubieCube::try_from(initial_state).unwrap();
+                COORDS.map(|coord| coord.index(&cube) as u32)
+            });
+        let search = Search {
+            ida: self,
+            use_bounds: indexes.is_some(),
+            move_count,
+            stop,
+        };
+        let indexes = indexes.unwrap_or_default();
+
+        if !multi_threaded {
+            search.search(
+                initial_state,
+                indexes,
+                &mut vec![initial_state],
+                &mut Vec::new(),
+                0,
+                found,
+            );
+            return;
+        }
+
+        // the threads send what they find to this one, which passes it on as it arrives
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for m in 0..self.moves.len() {
+                let (search, indexes, sender) = (&search, &indexes, sender.clone());
+                scope.spawn(move || {
+                    search.step(
+                        initial_state,
+                        indexes,
+                        &mut vec![initial_state],
+                        &mut Vec::new(),
+                        0,
+                        m,
+                        &mut |solution| {
+                            let _ = sender.send(solution);
+                        },
+                    );
+                });
+            }
+            drop(sender);
+            receiver.into_iter().for_each(found);
+        });
+    }
+}
+
+struct Search<'a> {
+    ida: &'a Ida,
+    /// Whether the indexes of the states are known, so the databases can be used
+    use_bounds: bool,
+    move_count: u8,
+    stop: &'a Stop,
+}
+
+impl Search<'_> {
+    fn search(
+        &self,
+        state: CubeState,
+        indexes: [u32; COORDS.len()],
+        prev_states: &mut Vec<CubeState>,
+        path: &mut Vec<Rotation>,
+        cost: u8,
+        found: &mut dyn FnMut(Solution),
+    ) {
+        if self.stop.is_set() {
+            return;
+        }
+        if cost == self.move_count {
+            if self.ida.goals.iter().any(|goal| goal.accepts(&state)) {
+                found(path.clone().into());
+            }
+            return;
+        }
+
+        for m in 0..self.ida.moves.len() {
+            self.step(state, &indexes, prev_states, path, cost, m, found);
+        }
+    }
+
+    /// Searches the paths going on with the given move, unless they can't reach a desired state
+    /// in time or have useless moves
+    #[allow(clippy::too_many_arguments)]
+    fn step(
+        &self,
+        state: CubeState,
+        indexes: &[u32; COORDS.len()],
+        prev_states: &mut Vec<CubeState>,
+        path: &mut Vec<Rotation>,
+        cost: u8,
+        m: usize,
+        found: &mut dyn FnMut(Solution),
+    ) {
+        let rot = self.ida.moves[m];
+        let new_cost = cost + self.ida.metric.rotation_cost(rot) as u8;
+        if new_cost > self.move_count || solution::is_rot_useless(path, rot) {
+            return;
+        }
+
+        let new_indexes = if self.use_bounds {
+            let new_indexes = std::array::from_fn(|coord| self.ida.next(coord, indexes[coord], m));
+            if self.ida.lower_bound(&new_indexes) > self.move_count - new_cost {
+                return;
+            }
+            new_indexes
+        } else {
+            *indexes
+        };
+
+        let new_state = state.rotate(rot);
+        if prev_states.contains(&new_state) {
+            return;
+        }
+
+        prev_states.push(new_state);
+        path.push(rot);
+        self.search(new_state, new_indexes, prev_states, path, new_cost, found);
+        path.pop();
+        prev_states.pop();
+    }
+}
@@ -1,34 +1,63 @@
+pub mod algorithm;
+pub mod auf;
+mod coord;
 pub mod cube;
+pub mod cubie;
 pub mod face;
+mod geometry;
+mod ida;
+pub mod metric;
+pub mod optimal;
+pub mod parser;
+pub mod pattern;
 pub mod rotation;
+pub mod scramble;
 pub mod solution;
 pub mod solver;
+pub mod tables;
+pub mod two_phase;
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeSet, HashSet};
+    use std::ops::ControlFlow;
     use std::str::FromStr;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
-    use crate::{cube::CubeState, rotation::Rotation};
+    use strum::IntoEnumIterator;
 
-fjksdjfksfj
+    use crate::algorithm::Algorithm;
+    use crate::auf::Auf;
+    use crate::cube::{FromStrErr, InvalidStateErr};
+    use crate::cubie::{CubieCube, CubieErr, SolveErr};
+    use crate::metric::Metric;
+    use crate::parser::{self, ParseErr};
+    use crate::pattern::{CubePattern, PatternFromStrErr};
+    use crate::scramble::SubsetErr;
+    use crate::solver::{CancelToken, IdaCache, Method};
+    use crate::{
+        cube::CubeState, geometry, optimal, rotation::Rotation, scramble, solution, solver, tables,
+        two_phase,
+    };
 
-    fn _solved_cube() -> CubeState {
-        CubeState::from_str(&String::from(
-            "WWWWWWWWWOOOOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBYYYYYYYYY",
-        ))
-        .unwrap()
+    fn solved_cube() -> CubeState {
+        CubeState::from_str(solved_cube_str()).unwrap()
     }
 
-jskdfjskjfdksj
+    fn solved_cube_str() -> &'static str {
+        "WWWWWWWWWOOOOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBYYYYYYYYY"
+    }
 
     /*
      * Cube in a cube in a cube
      */
     fn ccc() -> CubeState {
-        CubeState::from_str(&String::from(
-            "RWGRWWRRRYOBOOBBBBWWWWGGWGRGGGRRGWRGYBOYBBYYYBYOYYOOOO",
-        ))
-        .unwrap()
+        CubeState::from_str(ccc_str()).unwrap()
+    }
+
+    fn ccc_str() -> &'static str {
+        "RWGRWWRRRYOBOOBBBBWWWWGGWGRGGGRRGWRGYBOYBBYYYBYOYYOOOO"
     }
 
     #[test]
@@ -162,4 +191,1165 @@ jskdfjskjfdksj
         let state = state.rotate(Rotation::Dp);
         assert_eq!(dp_state, state);
     }
+
+    #[test]
+    fn double_rotations() {
+        let state = ccc();
+        for (double, quarter) in [
+            (Rotation::U2, Rotation::U),
+            (Rotation::L2, Rotation::L),
+            (Rotation::F2, Rotation::F),
+            (Rotation::R2, Rotation::R),
+            (Rotation::B2, Rotation::B),
+            (Rotation::D2, Rotation::D),
+        ] {
+            assert_eq!(state.rotate(double), state.rotate(quarter).rotate(quarter));
+            assert_eq!(
+                state.rotate(double),
+                state.rotate(quarter.reverse()).rotate(quarter.reverse())
+            );
+            assert_eq!(double.reverse(), double);
+        }
+    }
+
+    #[test]
+    fn useless_double_rotations() {
+        assert!(solution::is_rot_useless(&[Rotation::R2], Rotation::R));
+        assert!(solution::is_rot_useless(&[Rotation::R], Rotation::R2));
+        assert!(solution::is_rot_useless(&[Rotation::R], Rotation::R));
+        assert!(solution::is_rot_useless(
+            &[Rotation::R, Rotation::L2],
+            Rotation::Rp
+        ));
+        assert!(!solution::is_rot_useless(&[Rotation::R], Rotation::L2));
+        assert!(!solution::is_rot_useless(
+            &[Rotation::R2, Rotation::U],
+            Rotation::R2
+        ));
+    }
+
+    #[test]
+    fn face_permutations() {
+        let state = ccc();
+        for rot in Rotation::iter().filter(|rot| !rot.is_slice()) {
+            let permuted = state.permute(&geometry::PERMUTATIONS[rot as usize]);
+            assert_eq!(permuted, state.rotate(rot), "{}", rot);
+        }
+    }
+
+    #[test]
+    fn slice_rotations() {
+        let m_state = CubeState::from_str(&String::from(
+            "WBWWBWWBWOOOOOOOOOGWGGWGGWGRRRRRRRRRBYBBYBBYBYGYYGYYGY",
+        ))
+        .unwrap();
+        let e_state = CubeState::from_str(&String::from(
+            "WWWWWWWWWOOOBBBOOOGGGOOOGGGRRRGGGRRRBBBRRRBBBYYYYYYYYY",
+        ))
+        .unwrap();
+        let s_state = CubeState::from_str(&String::from(
+            "WWWOOOWWWOYOOYOOYOGGGGGGGGGRWRRWRRWRBBBBBBBBBYYYRRRYYY",
+        ))
+        .unwrap();
+        assert_eq!(solved_cube().rotate(Rotation::M), m_state);
+        assert_eq!(solved_cube().rotate(Rotation::E), e_state);
+        assert_eq!(solved_cube().rotate(Rotation::S), s_state);
+
+        let state = ccc();
+        for (slice, prime, double) in [
+            (Rotation::M, Rotation::Mp, Rotation::M2),
+            (Rotation::E, Rotation::Ep, Rotation::E2),
+            (Rotation::S, Rotation::Sp, Rotation::S2),
+        ] {
+            assert_eq!(state.rotate(slice).rotate(prime), state);
+            assert_eq!(state.rotate(double), state.rotate(slice).rotate(slice));
+            assert_eq!(state.rotate(double).rotate(double), state);
+        }
+    }
+
+    #[test]
+    fn useless_slice_rotations() {
+        assert!(solution::is_rot_useless(
+            &[Rotation::M, Rotation::R],
+            Rotation::Mp
+        ));
+        assert!(!solution::is_rot_useless(&[Rotation::M], Rotation::R));
+        assert!(!solution::is_rot_useless(&[Rotation::M], Rotation::E));
+    }
+
+    #[test]
+    fn wide_rotations() {
+        let state = ccc();
+        for (wide, face, slice) in [
+            (Rotation::Uw, Rotation::U, Rotation::Ep),
+            (Rotation::Lw, Rotation::L, Rotation::M),
+            (Rotation::Fw, Rotation::F, Rotation::S),
+            (Rotation::Rw, Rotation::R, Rotation::Mp),
+            (Rotation::Bw, Rotation::B, Rotation::Sp),
+            (Rotation::Dw, Rotation::D, Rotation::E),
+        ] {
+            assert_eq!(state.rotate(wide), state.rotate(face).rotate(slice));
+            assert_eq!(state.rotate(wide).rotate(wide.reverse()), state);
+        }
+
+        assert_eq!(Rotation::Rw.to_string(), "r");
+        assert_eq!(format!("{:#}", Rotation::Rwp), "Rw'");
+        assert_eq!(format!("{:#}", Rotation::R2), "R2");
+    }
+
+    #[test]
+    fn useless_wide_rotations() {
+        assert!(solution::is_rot_useless(&[Rotation::Rw], Rotation::Lp));
+        assert!(solution::is_rot_useless(&[Rotation::Rw], Rotation::Rp));
+        assert!(solution::is_rot_useless(&[Rotation::Rw], Rotation::M));
+        assert!(solution::is_rot_useless(
+            &[Rotation::R, Rotation::M],
+            Rotation::L
+        ));
+        assert!(!solution::is_rot_useless(&[Rotation::Rw], Rotation::U));
+        assert!(!solution::is_rot_useless(&[Rotation::R], Rotation::Lp));
+    }
+
+    #[test]
+    fn cube_rotations() {
+        let state = ccc();
+        assert_eq!(
+            state.reorient(Rotation::X),
+            state.rotate(Rotation::Rw).rotate(Rotation::Lp)
+        );
+        assert_eq!(
+            state.reorient(Rotation::Y),
+            state.rotate(Rotation::Uw).rotate(Rotation::Dp)
+        );
+        assert_eq!(
+            state.reorient(Rotation::Z),
+            state.rotate(Rotation::Fw).rotate(Rotation::Bp)
+        );
+        assert_eq!(state.reorient(Rotation::X2).reorient(Rotation::X2), state);
+
+        let orientations: HashSet<CubeState> = state.orientations().collect();
+        assert_eq!(orientations.len(), 24);
+    }
+
+    #[test]
+    fn orientation_normalized_comparison() {
+        let state = ccc();
+        let rotated = state.reorient(Rotation::Z).reorient(Rotation::Yp);
+        assert_ne!(state, rotated);
+        assert!(state.eq_up_to_rotation(&rotated));
+        assert!(!state.eq_up_to_rotation(&rotated.rotate(Rotation::U)));
+        assert_eq!(
+            state.canonical_orientation(),
+            rotated.canonical_orientation()
+        );
+        assert_eq!(rotated.canonical_orientation(), state);
+        assert_eq!(rotated.oriented_like(&state), Some(state));
+
+        // moves turning every layer of an axis aren't searched, so with the centers moving the
+        // desired state is only reached up to a cube rotation
+        let orientations: Vec<CubePattern> =
+            CubePattern::from(solved_cube()).orientations().collect();
+        assert_eq!(orientations.len(), 24);
+        let initial = solved_cube().reorient(Rotation::X).rotate(Rotation::U);
+        let found = solver::solve_any()
+            .initial_state(initial)
+            .desired_states(&orientations)
+            .move_count(1)
+            .wide_moves(true)
+            .call()
+            .solutions;
+        assert!(found.contains(&Algorithm::from(vec![Rotation::Up])));
+    }
+
+    #[test]
+    fn parse_moves() {
+        assert_eq!(Rotation::from_str("R'"), Ok(Rotation::Rp));
+        assert_eq!(Rotation::from_str("U2"), Ok(Rotation::U2));
+        assert_eq!(Rotation::from_str("Rw'"), Ok(Rotation::Rwp));
+        assert_eq!(Rotation::from_str("r'"), Ok(Rotation::Rwp));
+        assert_eq!(Rotation::from_str("x2"), Ok(Rotation::X2));
+        assert_eq!(Rotation::from_str("M3"), Ok(Rotation::Mp));
+        assert_eq!(Rotation::from_str("R4"), Err(ParseErr::EmptyMove(0)));
+        assert_eq!(
+            Rotation::from_str("X"),
+            Err(ParseErr::UnexpectedChar('X', 0))
+        );
+        assert_eq!(
+            Rotation::from_str("R U"),
+            Err(ParseErr::UnexpectedChar('U', 2))
+        );
+
+        for rot in Rotation::iter() {
+            assert_eq!(Rotation::from_str(&rot.to_string()), Ok(rot));
+            assert_eq!(Rotation::from_str(&format!("{:#}", rot)), Ok(rot));
+        }
+    }
+
+    #[test]
+    fn parse_algorithms() {
+        use Rotation::*;
+
+        let parse = |alg| parser::parse_algorithm(alg).map(Vec::from);
+
+        let sexy = vec![R, U, Rp, Up];
+        assert_eq!(parse("R U R' U'"), Ok(sexy.clone()));
+        assert_eq!(parse("RUR'U'"), Ok(sexy.clone()));
+        assert_eq!(parse("  R U  R'U' "), Ok(sexy.clone()));
+        assert_eq!(parse("[R, U]"), Ok(sexy.clone()));
+        assert_eq!(parse("(R U R' U')2"), Ok(sexy.repeat(2)));
+        assert_eq!(parse("(R U)'"), Ok(vec![Up, Rp]));
+        assert_eq!(parse("[F: R U R' U']"), Ok(vec![F, R, U, Rp, Up, Fp]));
+        assert_eq!(parse("[F: [R, U]]"), Ok(vec![F, R, U, Rp, Up, Fp]));
+        assert_eq!(parse("r U Rw'"), Ok(vec![Rw, U, Rwp]));
+        assert_eq!(parse(""), Ok(vec![]));
+
+        assert_eq!(parse("R U Q"), Err(ParseErr::UnexpectedChar('Q', 4)));
+        assert_eq!(parse("(R U"), Err(ParseErr::Unclosed('(', 0)));
+        assert_eq!(parse("R [U R"), Err(ParseErr::Unclosed('[', 2)));
+        assert_eq!(parse("[R U]"), Err(ParseErr::UnexpectedChar(']', 4)));
+        assert_eq!(parse("R U)"), Err(ParseErr::UnexpectedChar(')', 3)));
+        assert_eq!(parse("(R U)0"), Err(ParseErr::InvalidCount(5)));
+        assert_eq!(parse("(R U)4000000000"), Err(ParseErr::InvalidCount(5)));
+        assert_eq!(parse("((R U)100)100"), Err(ParseErr::InvalidCount(10)));
+    }
+
+    #[test]
+    fn algorithm_inverse() {
+        let alg = Algorithm::from_str("R U2 r' M E' x").unwrap();
+        assert_eq!(alg.inverse().to_string(), "x' E M' r U2 R'");
+        assert_eq!(alg.inverse().inverse(), alg);
+        assert_eq!((alg.clone() + alg.inverse()).apply_to(ccc()), ccc());
+    }
+
+    #[test]
+    fn algorithm_mirror() {
+        let sune = Algorithm::from_str("R U R' U R U2 R'").unwrap();
+        assert_eq!(sune.mirror_lr().to_string(), "L' U' L U' L' U2 L");
+        assert_eq!(sune.mirror_fb().to_string(), "R' U' R U' R' U2 R");
+
+        let alg = Algorithm::from_str("F r M S E u x y' z2").unwrap();
+        assert_eq!(alg.mirror_lr().to_string(), "F' l' M S' E' u' x y z2");
+        assert_eq!(alg.mirror_fb().to_string(), "B' r' M' S E' u' x' y z2");
+
+        // mirroring twice along the same axis gives back the algorithm
+        for rot in Rotation::iter() {
+            let alg = Algorithm::from(vec![rot]);
+            assert_eq!(alg.mirror_lr().mirror_lr(), alg);
+            assert_eq!(alg.mirror_fb().mirror_fb(), alg);
+        }
+    }
+
+    #[test]
+    fn algorithm_order_and_display() {
+        use Rotation::*;
+
+        let algs: BTreeSet<Algorithm> = ["R U R'", "U", "R U", "F", "U R"]
+            .into_iter()
+            .map(|alg| Algorithm::from_str(alg).unwrap())
+            .collect();
+        let sorted: Vec<String> = algs.iter().map(|alg| alg.to_string()).collect();
+        assert_eq!(sorted, ["U", "F", "U R", "R U", "R U R'"]);
+
+        let alg = Algorithm::from(vec![Rw, U, Rp, Up]);
+        assert_eq!(alg.to_string(), "r U R' U'");
+        assert_eq!(format!("{:#}", alg), "Rw U R' U'");
+        assert_eq!(Algorithm::from_str(&alg.to_string()), Ok(alg.clone()));
+        assert_eq!(Algorithm::new().to_string(), "");
+        assert_eq!(
+            Algorithm::from(vec![Rw, U]) + Algorithm::from(vec![Rp, Up]),
+            alg
+        );
+    }
+
+    #[test]
+    fn algorithm_metrics() {
+        let alg = Algorithm::from_str("R U2 M' r2 E2 x").unwrap();
+        assert_eq!(alg.len_in(Metric::Htm), 7);
+        assert_eq!(alg.len_in(Metric::Qtm), 11);
+        assert_eq!(alg.len_in(Metric::Stm), 5);
+        assert_eq!(alg.len_in(Metric::Qstm), 8);
+        assert_eq!(alg.len_in(Metric::Etm), 6);
+    }
+
+    #[test]
+    fn solve_in_metrics() {
+        let sexy = Algorithm::from_str("R U R' U'").unwrap();
+        let target = sexy.apply_to(solved_cube());
+        for multi_threaded in [false, true] {
+            let found = solver::solve()
+                .initial_state(solved_cube())
+                .desired_state(target)
+                .move_count(4)
+                .multi_threaded(multi_threaded)
+                .call()
+                .solutions;
+            assert!(found.contains(&sexy));
+        }
+
+        let m2 = Algorithm::from_str("M2").unwrap();
+        let target = m2.apply_to(solved_cube());
+        for (metric, move_count) in [(Metric::Htm, 2), (Metric::Qtm, 4), (Metric::Stm, 1)] {
+            for multi_threaded in [false, true] {
+                let found = solver::solve()
+                    .initial_state(solved_cube())
+                    .desired_state(target)
+                    .move_count(move_count)
+                    .multi_threaded(multi_threaded)
+                    .slice_moves(true)
+                    .metric(metric)
+                    .call()
+                    .solutions;
+                assert!(found.contains(&m2));
+                for alg in found {
+                    assert_eq!(alg.apply_to(solved_cube()), target);
+                    assert_eq!(alg.len_in(metric), move_count as u32);
+                }
+            }
+        }
+
+        // slices count as a single move by default
+        let h_perm = Algorithm::from_str("M2 U M2 U2 M2 U M2").unwrap();
+        assert!(solver::solve()
+            .initial_state(h_perm.inverse().apply_to(solved_cube()))
+            .desired_state(solved_cube())
+            .move_count(7)
+            .slice_moves(true)
+            .call()
+            .solutions
+            .contains(&h_perm));
+    }
+
+    #[test]
+    fn algorithm_simplify() {
+        let simplify = |alg| Algorithm::from_str(alg).unwrap().simplify().to_string();
+        assert_eq!(simplify("R R"), "R2");
+        assert_eq!(simplify("R R'"), "");
+        assert_eq!(simplify("R2 R"), "R'");
+        assert_eq!(simplify("R L R'"), "L");
+        assert_eq!(simplify("R L2 R L2"), "R2");
+        assert_eq!(simplify("R U U' R'"), "");
+        assert_eq!(simplify("F R U2 U2 R' F'"), "");
+        assert_eq!(simplify("R U R' U'"), "R U R' U'");
+        assert_eq!(simplify("x x y' y'"), "x2 y2");
+        assert_eq!(simplify("r R'"), "r R'");
+
+        let simplify_layers = |alg| {
+            Algorithm::from_str(alg)
+                .unwrap()
+                .simplify_layers()
+                .to_string()
+        };
+        assert_eq!(simplify_layers("r R'"), "M'");
+        assert_eq!(simplify_layers("R M'"), "r");
+        assert_eq!(simplify_layers("R M' L'"), "x");
+        assert_eq!(simplify_layers("r' x"), "L'");
+        assert_eq!(simplify_layers("R L'"), "R L'");
+        assert_eq!(simplify_layers("R U r' M' R U' R'"), "");
+
+        for alg in [
+            "R U2 r' M E' x l2 L' S",
+            "F r M S E u x y' z2 d b2",
+            "R' M2 r2 l' L",
+        ] {
+            let alg = Algorithm::from_str(alg).unwrap();
+            assert_eq!(alg.simplify().apply_to(ccc()), alg.apply_to(ccc()));
+            assert_eq!(alg.simplify_layers().apply_to(ccc()), alg.apply_to(ccc()));
+        }
+    }
+
+    #[test]
+    fn scrambled_states() {
+        assert_eq!(CubeState::solved(), solved_cube());
+        assert_eq!(CubeState::from_scramble(""), Ok(solved_cube()));
+        assert_eq!(CubeState::from_scramble("(R U R' U')6"), Ok(solved_cube()));
+        assert_eq!(
+            CubeState::from_scramble("U"),
+            Ok(solved_cube().rotate(Rotation::U))
+        );
+        assert_eq!(
+            CubeState::from_scramble("R U R' Q"),
+            Err(ParseErr::UnexpectedChar('Q', 7))
+        );
+    }
+
+    #[test]
+    fn cubie_conversion() {
+        let solved = CubieCube::try_from(solved_cube()).unwrap();
+        assert!(solved.is_solved());
+        assert_eq!(CubeState::from(CubieCube::SOLVED), solved_cube());
+
+        let cube = CubieCube::try_from(ccc()).unwrap();
+        assert_eq!(CubeState::from(cube), ccc());
+
+        // the same cube held differently has the same pieces relative to the centers
+        let rotated = ccc().reorient(Rotation::X).reorient(Rotation::Yp);
+        let rotated_cube = CubieCube::try_from(rotated).unwrap();
+        assert_eq!(CubeState::from(rotated_cube), rotated);
+        assert_eq!(rotated_cube.normalized(), cube);
+        assert!(
+            CubieCube::try_from(CubeState::solved().reorient(Rotation::Z))
+                .unwrap()
+                .is_solved()
+        );
+
+        let unspecified = "N".to_string() + &ccc_str()[1..];
+        assert!(matches!(
+            CubieCube::try_from(CubeState::from_str(&unspecified).unwrap()),
+            Err(CubieErr::Unspecified(_))
+        ));
+        assert_eq!(
+            CubieCube::try_from(CubeState::from_str(&"W".repeat(54)).unwrap()),
+            Err(CubieErr::InvalidCenters)
+        );
+    }
+
+    #[test]
+    fn cubie_moves() {
+        let cube = CubieCube::try_from(ccc()).unwrap();
+        for rot in Rotation::iter() {
+            assert_eq!(
+                CubieCube::try_from(ccc().rotate(rot)),
+                Ok(cube.rotate(rot)),
+                "{}",
+                rot
+            );
+        }
+
+        let alg = Algorithm::from_str("R U2 r' M E' x l2 L' S F").unwrap();
+        let moved = alg.iter().fold(cube, |cube, &rot| cube.rotate(rot));
+        assert_eq!(CubieCube::try_from(alg.apply_to(ccc())), Ok(moved));
+
+        let scramble = alg
+            .iter()
+            .fold(CubieCube::SOLVED, |cube, &rot| cube.rotate(rot));
+        assert_eq!(cube.compose(&scramble), moved);
+        assert!(moved.compose(&moved.inverse()).is_solved());
+        assert!(moved.inverse().compose(&moved).is_solved());
+        assert_eq!(moved.compose(&scramble.inverse()), cube);
+    }
+
+    #[test]
+    fn state_validation() {
+        assert_eq!(solved_cube().validate(), Ok(()));
+        assert_eq!(ccc().validate(), Ok(()));
+        assert_eq!(
+            CubeState::from_scramble("R U2 r' M E' x l2 L' S F")
+                .unwrap()
+                .validate(),
+            Ok(())
+        );
+
+        let too_many_whites = "W".repeat(10) + &solved_cube_str()[10..];
+        assert_eq!(
+            CubeState::from_str(&too_many_whites).unwrap().validate(),
+            Err(InvalidStateErr::ColorCount('W', 10))
+        );
+
+        let mut cells = [0; geometry::CELL_COUNT];
+        for (idx, cell) in cells.iter_mut().enumerate() {
+            *cell = solved_cube().cell(idx as u8);
+        }
+        cells.swap(3, 43);
+        assert_eq!(
+            CubeState::from_cells(&cells).validate(),
+            Err(InvalidStateErr::Corner("URF"))
+        );
+
+        // the extra red and missing orange stickers are compensated with DBL
+        let mut cube = CubieCube::SOLVED;
+        cube.cp[1] = 0;
+        cube.cp[7] = 6;
+        assert_eq!(
+            CubeState::from(cube).validate(),
+            Err(InvalidStateErr::DuplicateCorner("URF"))
+        );
+
+        let mut cube = CubieCube::SOLVED;
+        cube.co[0] = 2;
+        assert_eq!(
+            CubeState::from(cube).validate(),
+            Err(InvalidStateErr::Twist(2))
+        );
+
+        let mut cube = CubieCube::SOLVED;
+        cube.eo[5] = 1;
+        assert_eq!(CubeState::from(cube).validate(), Err(InvalidStateErr::Flip));
+
+        let mut cube = CubieCube::SOLVED;
+        cube.ep.swap(0, 1);
+        assert_eq!(
+            CubeState::from(cube).validate(),
+            Err(InvalidStateErr::Parity)
+        );
+
+        // unspecified stickers can be anything
+        let partial = "N".repeat(9) + &solved_cube_str()[9..];
+        assert_eq!(CubeState::from_str(&partial).unwrap().validate(), Ok(()));
+        let partial = "NNNNNNNNNOOOOOOOOOGGGGGGGGGRRRRRRRRRBBBBBBBBBYYYYYYYYY";
+        assert_eq!(CubeState::from_str(partial).unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn partial_state_reachability() {
+        let without = |state: CubeState, pieces: &[&[u8]]| {
+            let mut cells = [0; geometry::CELL_COUNT];
+            for (idx, cell) in cells.iter_mut().enumerate() {
+                *cell = state.cell(idx as u8);
+            }
+            for &piece_cells in pieces {
+                for &cell in piece_cells {
+                    cells[cell as usize] = 0;
+                }
+            }
+            CubeState::from_cells(&cells)
+        };
+        let edges: Vec<&[u8]> = geometry::EDGE_CELLS.iter().map(|it| &it[..]).collect();
+
+        assert!(solved_cube().can_reach(&ccc(), false));
+        assert!(CubeState::from_str(&"N".repeat(54))
+            .unwrap()
+            .is_completable());
+
+        // a twisted corner can't be fixed by unspecified edges
+        let mut twisted = CubieCube::SOLVED;
+        twisted.co[0] = 1;
+        assert!(!solved_cube().can_reach(&without(twisted.into(), &edges), true));
+        // but it can by an unspecified corner
+        assert!(solved_cube().can_reach(
+            &without(twisted.into(), &[&geometry::CORNER_CELLS[4]]),
+            true
+        ));
+        // a corner that could only be the one already in place somewhere else
+        let mut duplicate = CubieCube::SOLVED;
+        duplicate.cp[3] = 0;
+        assert!(!without(duplicate.into(), &edges).is_completable());
+
+        // swapped corners need swapped edges
+        let mut swapped = CubieCube::SOLVED;
+        swapped.cp.swap(0, 1);
+        swapped.ep.swap(0, 1);
+        assert!(swapped.corner_parity() && swapped.edge_parity());
+        let corners_only = without(swapped.into(), &edges);
+        assert!(solved_cube().can_reach(&corners_only, false));
+        let mut cells = [0; geometry::CELL_COUNT];
+        for (idx, cell) in cells.iter_mut().enumerate() {
+            *cell = if geometry::EDGE_CELLS
+                .iter()
+                .flatten()
+                .any(|&it| it as usize == idx)
+            {
+                solved_cube().cell(idx as u8)
+            } else {
+                corners_only.cell(idx as u8)
+            };
+        }
+        assert!(!solved_cube().can_reach(&CubeState::from_cells(&cells), false));
+
+        // centers only move with slices, wide moves or cube rotations
+        let rotated = ccc().reorient(Rotation::X);
+        assert!(!solved_cube().can_reach(&rotated, false));
+        assert!(solved_cube().can_reach(&rotated, true));
+    }
+
+    #[test]
+    fn solve_partial_states() {
+        // the last layer is solved no matter what happens to the bottom face
+        let sexy = Algorithm::from_str("R U R' U'").unwrap();
+        let target = sexy.apply_to(solved_cube());
+        let mut cells = [0; geometry::CELL_COUNT];
+        for (idx, cell) in cells.iter_mut().enumerate() {
+            *cell = if (41..=48).contains(&idx) {
+                0
+            } else {
+                target.cell(idx as u8)
+            };
+        }
+        let partial = CubeState::from_cells(&cells);
+        assert!(partial.is_partial() && !target.is_partial());
+        assert!(target.matches(&partial));
+        assert!(!solved_cube().matches(&partial));
+        assert!(!partial.matches(&target));
+
+        for multi_threaded in [false, true] {
+            let found = solver::solve()
+                .initial_state(solved_cube())
+                .desired_state(partial)
+                .move_count(4)
+                .multi_threaded(multi_threaded)
+                .call()
+                .solutions;
+            assert!(found.contains(&sexy));
+            for alg in found {
+                assert!(alg.apply_to(solved_cube()).matches(&partial));
+            }
+        }
+    }
+
+    #[test]
+    fn color_set_patterns() {
+        let u_face = format!("{}{}", "[WY]".repeat(9), "N".repeat(45));
+        let pattern = CubePattern::from_str(&u_face).unwrap();
+        assert_eq!(pattern.to_string(), u_face);
+        assert!(pattern.accepts(&solved_cube()));
+        assert!(pattern.accepts(&solved_cube().rotate(Rotation::R2)));
+        assert!(!pattern.accepts(&solved_cube().rotate(Rotation::R)));
+        assert_eq!(
+            pattern.relaxed(),
+            CubeState::from_str(&"N".repeat(54)).unwrap()
+        );
+
+        let not_yellow =
+            CubePattern::from_str(&format!("[^Y]{}", &solved_cube_str()[1..])).unwrap();
+        assert!(not_yellow.accepts(&solved_cube()));
+        assert_eq!(
+            not_yellow.relaxed(),
+            CubeState::from_str(&format!("N{}", &solved_cube_str()[1..])).unwrap()
+        );
+        assert_eq!(
+            CubePattern::from(solved_cube()),
+            CubePattern::from_str(solved_cube_str()).unwrap()
+        );
+
+        let invalid = |pattern: String| CubePattern::from_str(&pattern).unwrap_err();
+        assert!(matches!(
+            invalid(format!("[WY{}", "W".repeat(53))),
+            PatternFromStrErr::UnclosedSet
+        ));
+        assert!(matches!(
+            invalid(format!("[^WOGRBY]{}", "W".repeat(53))),
+            PatternFromStrErr::EmptySet
+        ));
+        assert!(matches!(
+            invalid(format!("[WN]{}", "W".repeat(53))),
+            PatternFromStrErr::State(FromStrErr::InvalidChar('N'))
+        ));
+        assert!(matches!(
+            invalid("[WY]".repeat(53)),
+            PatternFromStrErr::State(FromStrErr::InvalidLen)
+        ));
+
+        for multi_threaded in [false, true] {
+            let found = solver::solve()
+                .initial_state(solved_cube())
+                .desired_state(pattern)
+                .move_count(1)
+                .multi_threaded(multi_threaded)
+                .call()
+                .solutions;
+            assert!(found.contains(&Algorithm::from_str("R2").unwrap()));
+            assert!(!found.contains(&Algorithm::from_str("R").unwrap()));
+            for alg in found {
+                assert!(pattern.accepts(&alg.apply_to(solved_cube())));
+            }
+        }
+    }
+
+    #[test]
+    fn solve_any_goal() {
+        // solved up to the last U move
+        let goals: Vec<CubePattern> = ["", "U", "U'", "U2"]
+            .iter()
+            .map(|auf| CubePattern::from(Algorithm::from_str(auf).unwrap().apply_to(solved_cube())))
+            .collect();
+        let initial = solved_cube().rotate(Rotation::R);
+
+        let mut found = Vec::new();
+        for multi_threaded in [false, true] {
+            found.push(
+                solver::solve_any()
+                    .initial_state(initial)
+                    .desired_states(&goals)
+                    .move_count(2)
+                    .multi_threaded(multi_threaded)
+                    .call()
+                    .solutions,
+            );
+        }
+        assert_eq!(found[0], found[1]);
+        for alg in ["R' U", "R' U'", "R' U2"] {
+            assert!(found[0].contains(&Algorithm::from_str(alg).unwrap()));
+        }
+        for alg in &found[0] {
+            let state = alg.apply_to(initial);
+            assert!(goals.iter().any(|goal| goal.accepts(&state)));
+        }
+
+        let single = solver::solve()
+            .initial_state(initial)
+            .desired_state(solved_cube())
+            .move_count(1)
+            .call()
+            .solutions;
+        let any = solver::solve_any()
+            .initial_state(initial)
+            .desired_states(&goals[..1])
+            .move_count(1)
+            .call()
+            .solutions;
+        assert_eq!(single, any);
+        assert_eq!(single, vec![Algorithm::from_str("R'").unwrap()]);
+    }
+
+    #[test]
+    fn solve_with_auf() {
+        let antisune = Algorithm::from_str("R U2 R' U' R U' R'").unwrap();
+        let initial = Algorithm::from_str("U R U R' U R U2 R' U2")
+            .unwrap()
+            .apply_to(solved_cube());
+        let solve = |auf| {
+            solver::solve()
+                .initial_state(initial)
+                .desired_state(solved_cube())
+                .move_count(7)
+                .auf(auf)
+                .call()
+                .solutions
+        };
+
+        assert!(solve(Auf::None).is_empty());
+        assert!(!solve(Auf::Pre).contains(&antisune));
+        let found = solve(Auf::Both);
+        assert!(found.contains(&antisune));
+        assert_eq!(
+            Auf::Both.adjustments(initial, &[solved_cube().into()], &antisune),
+            Some((Some(Rotation::U2), Some(Rotation::Up)))
+        );
+        for alg in &found {
+            assert!(!Auf::Both.wastes_moves(alg));
+            assert!(Auf::Both
+                .adjustments(initial, &[solved_cube().into()], alg)
+                .is_some());
+        }
+
+        assert!(Auf::Pre.wastes_moves(&Algorithm::from_str("D U R").unwrap()));
+        assert!(!Auf::Pre.wastes_moves(&Algorithm::from_str("R U").unwrap()));
+        assert!(Auf::Post.wastes_moves(&Algorithm::from_str("R U").unwrap()));
+        assert_eq!(Auf::from_str("BOTH"), Ok(Auf::Both));
+    }
+
+    #[test]
+    fn random_state_scrambles() {
+        let mut rng = scramble::rng(Some(42));
+        for _ in 0..5 {
+            let cube = scramble::random_cube(&mut rng);
+            assert_eq!(CubeState::from(cube).validate(), Ok(()));
+            let solution = two_phase::solve_cubie(&cube, 30).unwrap();
+            assert!(solution.len() <= 30);
+            assert!(solution.iter().all(|rot| !rot.is_slice() && !rot.is_wide()));
+            assert_eq!(solution.apply_to(cube.into()), solved_cube());
+        }
+
+        let scramble = scramble::random_state(Some(7));
+        assert_eq!(scramble, scramble::random_state(Some(7)));
+        assert_ne!(scramble, scramble::random_state(Some(8)));
+        assert_eq!(
+            scramble.apply_to(solved_cube()),
+            CubeState::from(scramble::random_cube(&mut scramble::rng(Some(7))))
+        );
+    }
+
+    #[test]
+    fn random_move_scrambles() {
+        let faces = scramble::face_turns();
+        assert_eq!(faces.len(), 18);
+        let moves = scramble::random_moves(Some(3), 25, &faces);
+        assert_eq!(moves.len(), 25);
+        assert_eq!(moves, scramble::random_moves(Some(3), 25, &faces));
+        assert!(!solution::has_useless_moves(solved_cube(), &moves));
+
+        let subset = scramble::parse_subset("<R,U>").unwrap();
+        assert_eq!(
+            subset,
+            parser::parse_algorithm("R R' R2 U U' U2").unwrap().to_vec()
+        );
+        assert_eq!(scramble::parse_subset("R U").unwrap(), subset);
+        assert!(matches!(
+            scramble::parse_subset("<R,Q>"),
+            Err(SubsetErr::Parse(_))
+        ));
+        assert_eq!(
+            scramble::parse_subset("R x"),
+            Err(SubsetErr::CubeRotation(Rotation::X))
+        );
+        assert_eq!(scramble::parse_subset("<>"), Err(SubsetErr::Empty));
+        let two_gen = scramble::random_moves(Some(3), 12, &subset);
+        assert_eq!(two_gen.len(), 12);
+        assert!(two_gen.iter().all(|rot| subset.contains(rot)));
+        // nothing can follow a move when there is a single layer
+        assert_eq!(scramble::random_moves(None, 5, &[Rotation::R]).len(), 1);
+
+        let mut rng = scramble::rng(Some(5));
+        for _ in 0..3 {
+            let cube = scramble::random_last_layer_cube(&mut rng);
+            assert_eq!(CubeState::from(cube).validate(), Ok(()));
+            assert_eq!(cube.cp[4..], CubieCube::SOLVED.cp[4..]);
+            assert_eq!(cube.ep[4..], CubieCube::SOLVED.ep[4..]);
+        }
+        let last_layer = scramble::last_layer(Some(5));
+        let f2l = CubeState::from_str(&format!(
+            "{}{}",
+            "N".repeat(9),
+            "OOOOOONNNGGGGGGNNNRRRRRRNNNBBBBBBNNNYYYYYYYYY"
+        ))
+        .unwrap();
+        assert!(last_layer.apply_to(solved_cube()).matches(&f2l));
+    }
+
+    #[test]
+    fn two_phase_solver() {
+        let solve = |state| two_phase::solve().state(state).call();
+        assert_eq!(solve(solved_cube()), Ok(Algorithm::new()));
+        let state = scramble::random_state(Some(11)).apply_to(solved_cube());
+        let solution = solve(state).unwrap();
+        assert!(solution.len() <= 21);
+        assert_eq!(solution.apply_to(state), solved_cube());
+
+        // held differently, it is solved relative to the centers
+        let rotated = state.reorient(Rotation::X);
+        assert!(solve(rotated)
+            .unwrap()
+            .apply_to(rotated)
+            .eq_up_to_rotation(&solved_cube()));
+
+        let sexy = Algorithm::from_str("R U R' U'")
+            .unwrap()
+            .apply_to(solved_cube());
+        assert_eq!(
+            two_phase::solve().state(sexy).max_length(3).call(),
+            Err(SolveErr::NotFound(3))
+        );
+        assert_eq!(
+            solve(CubeState::from_str(&"N".repeat(54)).unwrap()),
+            Err(SolveErr::Partial)
+        );
+        let mut twisted = CubieCube::SOLVED;
+        twisted.co[0] = 1;
+        assert_eq!(
+            solve(twisted.into()),
+            Err(SolveErr::Invalid(InvalidStateErr::Twist(1)))
+        );
+    }
+
+    #[test]
+    fn optimal_solver() {
+        let solve = |state| optimal::solve().state(state).call();
+        assert_eq!(solve(solved_cube()).unwrap().length, 0);
+
+        let state = Algorithm::from_str("R U R' U' F2 D")
+            .unwrap()
+            .apply_to(solved_cube());
+        let optimal = solve(state).unwrap();
+        assert_eq!(optimal.length, 6);
+        assert_eq!(optimal.solutions.len(), 1);
+        assert_eq!(optimal.solutions[0].apply_to(state), solved_cube());
+
+        // every solution of that length, the same ones the bidirectional solver finds
+        let all = optimal::solve()
+            .state(state)
+            .all_solutions(true)
+            .multi_threaded(true)
+            .call()
+            .unwrap();
+        let found = solver::solve()
+            .initial_state(state)
+            .desired_state(solved_cube())
+            .move_count(6)
+            .call()
+            .solutions;
+        assert_eq!(
+            all.solutions.into_iter().collect::<HashSet<_>>(),
+            found.into_iter().collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            optimal::solve().state(state).max_length(5).call(),
+            Err(SolveErr::NotFound(5))
+        );
+        assert_eq!(
+            solve(CubeState::from_str(&"N".repeat(54)).unwrap()),
+            Err(SolveErr::Partial)
+        );
+    }
+
+    #[test]
+    fn cached_tables() {
+        let dir = std::env::temp_dir().join(format!("cuberithm-tables-{}", std::process::id()));
+        let builds = std::cell::Cell::new(0);
+        let load = |version| {
+            let table = tables::load_or_build_in(Some(&dir), "test", version, || {
+                builds.set(builds.get() + 1);
+                vec![1, 2, 3]
+            });
+            (table.to_vec(), matches!(table, tables::Table::Mapped(_)))
+        };
+
+        assert_eq!(load(1), (vec![1, 2, 3], false));
+        assert_eq!(load(1), (vec![1, 2, 3], true));
+        assert_eq!(builds.get(), 1);
+
+        // another version of the table is built again
+        assert_eq!(load(2), (vec![1, 2, 3], false));
+        assert_eq!(load(2), (vec![1, 2, 3], true));
+        assert_eq!(builds.get(), 2);
+
+        // and so is a damaged one
+        let path = dir.join("test.tbl");
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() = 4;
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(load(2), (vec![1, 2, 3], false));
+        assert_eq!(builds.get(), 3);
+
+        assert_eq!(
+            tables::load_or_build_in(None, "test", 1, || vec![5]).to_vec(),
+            vec![5]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the tables the other tests build stay out of the user's cache
+        if std::env::var_os(tables::CACHE_DIR_VAR).is_none() {
+            assert!(tables::cache_dir()
+                .unwrap()
+                .starts_with(std::env::temp_dir()));
+        }
+    }
+
+    #[test]
+    fn ida_generator() {
+        let sune = Algorithm::from_str("R U R' U R U2 R'").unwrap();
+        let sune_case = sune.inverse().apply_to(solved_cube());
+        let sexy = Algorithm::from_str("R U R' U'")
+            .unwrap()
+            .apply_to(solved_cube());
+        let u_face =
+            CubePattern::from_str(&format!("{}{}", "W".repeat(9), "N".repeat(45))).unwrap();
+        let last_layer = CubePattern::from_str(&format!(
+            "{}{}{}",
+            "[WY]".repeat(9),
+            "N".repeat(36),
+            "Y".repeat(9)
+        ))
+        .unwrap();
+
+        let ida_cache = IdaCache::new();
+        // the same algorithms as meeting in the middle, for partial states and color sets as
+        // well, including the ones whose last moves only move unspecified stickers
+        for (initial, desired_state, move_count, metric, slice_moves, auf) in [
+            (
+                sune_case,
+                solved_cube().into(),
+                7,
+                Metric::Htm,
+                false,
+                Auf::None,
+            ),
+            (sexy, u_face, 5, Metric::Htm, false, Auf::Pre),
+            (sexy, u_face, 4, Metric::Qtm, true, Auf::None),
+            (sexy, last_layer, 5, Metric::Stm, true, Auf::Post),
+        ] {
+            let solve = |method, multi_threaded| {
+                solver::solve()
+                    .initial_state(initial)
+                    .desired_state(desired_state)
+                    .move_count(move_count)
+                    .metric(metric)
+                    .slice_moves(slice_moves)
+                    .auf(auf)
+                    .method(method)
+                    .multi_threaded(multi_threaded)
+                    .ida_cache(&ida_cache)
+                    .call()
+                    .solutions
+            };
+            let found = solve(Method::Ida, false);
+            assert!(!found.is_empty());
+            assert_eq!(found, solve(Method::Bidirectional, false));
+            assert_eq!(found, solve(Method::Bidirectional, true));
+            assert_eq!(found, solve(Method::Ida, true));
+        }
+        assert!(solver::solve()
+            .initial_state(sune_case)
+            .desired_state(solved_cube())
+            .move_count(7)
+            .method(Method::Ida)
+            .call()
+            .solutions
+            .contains(&sune));
+
+        assert_eq!(Method::from_str("IDA"), Ok(Method::Ida));
+        assert_eq!(Method::Bidirectional.to_string(), "bidirectional");
+        assert!(Method::from_str("bfs").is_err());
+    }
+
+    #[test]
+    fn memory_budget() {
+        let sune = Algorithm::from_str("R U R' U R U2 R'").unwrap();
+        let initial = sune.inverse().apply_to(solved_cube());
+
+        // enough memory, a first pass of 1 move instead of 3, and no first pass fitting at all
+        let found: Vec<_> = [None, Some(1000), Some(0)]
+            .into_iter()
+            .flat_map(|max_memory| [(max_memory, false), (max_memory, true)])
+            .map(|(max_memory, multi_threaded)| {
+                solver::solve()
+                    .initial_state(initial)
+                    .desired_state(solved_cube())
+                    .move_count(7)
+                    .multi_threaded(multi_threaded)
+                    .maybe_max_memory(max_memory)
+                    .call()
+                    .solutions
+            })
+            .collect();
+        assert!(found[0].contains(&sune));
+        assert!(found.iter().all(|it| *it == found[0]));
+
+        // partial desired states use IDA* with a budget, whatever it is
+        let oll =
+            CubeState::from_str("WWWWWWWWWOOOOOONNNGGGGGGNNNRRRRRRNNNBBBBBBNNNYYYYYYYYY").unwrap();
+        let found: Vec<_> = [None, Some(usize::MAX)]
+            .into_iter()
+            .map(|max_memory| {
+                solver::solve()
+                    .initial_state(initial)
+                    .desired_state(oll)
+                    .move_count(7)
+                    .method(Method::Bidirectional)
+                    .maybe_max_memory(max_memory)
+                    .call()
+                    .solutions
+            })
+            .collect();
+        assert!(found[0].contains(&sune));
+        assert_eq!(found[0], found[1]);
+    }
+
+    #[test]
+    fn streamed_solutions() {
+        let initial = Algorithm::from_str("R U R' U R U2 R'")
+            .unwrap()
+            .inverse()
+            .apply_to(solved_cube());
+        let desired_states = [solved_cube().into()];
+
+        for (method, multi_threaded) in [
+            (Method::Bidirectional, false),
+            (Method::Bidirectional, true),
+            (Method::Ida, true),
+        ] {
+            let stream = |max_solutions: usize| {
+                let mut streamed = Vec::new();
+                let flow = solver::solve_any_each()
+                    .initial_state(initial)
+                    .desired_states(&desired_states)
+                    .move_count(7)
+                    .auf(Auf::Both)
+                    .method(method)
+                    .multi_threaded(multi_threaded)
+                    .on_solution(|solution| {
+                        streamed.push(solution);
+                        if streamed.len() < max_solutions {
+                            ControlFlow::Continue(())
+                        } else {
+                            ControlFlow::Break(())
+                        }
+                    })
+                    .call();
+                (flow, streamed)
+            };
+
+            let (flow, mut streamed) = stream(usize::MAX);
+            streamed.sort();
+            assert_eq!(flow, ControlFlow::Continue(()));
+            assert!(streamed.len() > 1);
+            assert_eq!(
+                streamed,
+                solver::solve_any()
+                    .initial_state(initial)
+                    .desired_states(&desired_states)
+                    .move_count(7)
+                    .auf(Auf::Both)
+                    .method(method)
+                    .call()
+                    .solutions
+            );
+
+            let (flow, first) = stream(1);
+            assert_eq!(flow, ControlFlow::Break(()));
+            assert_eq!(first.len(), 1);
+            assert!(streamed.contains(&first[0]));
+        }
+    }
+
+    #[test]
+    fn stopped_search() {
+        let sune = Algorithm::from_str("R U R' U R U2 R'").unwrap();
+        let search = |method, max_solutions, cancel, timeout| {
+            solver::solve()
+                .initial_state(sune.inverse().apply_to(solved_cube()))
+                .desired_state(solved_cube())
+                .move_count(7)
+                .auf(Auf::Both)
+                .method(method)
+                .multi_threaded(true)
+                .maybe_max_solutions(max_solutions)
+                .cancel(cancel)
+                .maybe_timeout(timeout)
+                .call()
+        };
+
+        for method in [Method::Bidirectional, Method::Ida] {
+            let all = search(method, None, CancelToken::new(), None);
+            assert!(all.complete && all.solutions.len() > 1);
+
+            let first = search(method, Some(1), CancelToken::new(), None);
+            assert!(!first.complete);
+            assert_eq!(first.solutions.len(), 1);
+            assert!(all.solutions.contains(&first.solutions[0]));
+
+            let cancel = CancelToken::new();
+            cancel.cancel();
+            let cancelled = search(method, None, cancel, None);
+            assert!(!cancelled.complete && cancelled.solutions.is_empty());
+        }
+
+        // far too long to finish, stopped by a timeout or another thread
+        let long_search = |cancel, timeout| {
+            solver::solve()
+                .initial_state(ccc())
+                .desired_state(solved_cube())
+                .move_count(16)
+                .multi_threaded(true)
+                .cancel(cancel)
+                .maybe_timeout(timeout)
+                .call()
+        };
+        let start = Instant::now();
+        let timed_out = long_search(CancelToken::new(), Some(Duration::from_millis(100)));
+        assert!(!timed_out.complete);
+
+        let cancel = CancelToken::new();
+        let cancelled = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(100));
+                cancel.cancel();
+            });
+            long_search(cancel.clone(), None)
+        });
+        assert!(!cancelled.complete);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // the databases of Method::Ida stop being built as well
+        let start = Instant::now();
+        let building = solver::solve()
+            .initial_state(ccc())
+            .desired_state(solved_cube())
+            .move_count(16)
+            .method(Method::Ida)
+            .timeout(Duration::from_millis(1))
+            .call();
+        assert!(!building.complete && building.solutions.is_empty());
+        // building them whole takes most of a second
+        assert!(start.elapsed() < Duration::from_millis(250));
+    }
 }
@@ -0,0 +1,82 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::rotation::Rotation;
+
+/// Ways of counting the length of an algorithm
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Metric {
+    /// Half turn metric, any turn of an outer layer (or two adjacent ones) counts as one,
+    /// slices count as two and cube rotations are free
+    #[default]
+    Htm,
+    /// Quarter turn metric, like HTM but half turns count as two
+    Qtm,
+    /// Slice turn metric, any turn of any layers counts as one, cube rotations are free
+    Stm,
+    /// Quarter slice turn metric, like STM but half turns count as two
+    Qstm,
+    /// Execution turn metric, every move counts as one, cube rotations included
+    Etm,
+}
+
+impl Metric {
+    /// Cost of a single rotation in this metric
+    pub fn rotation_cost(&self, rot: Rotation) -> u32 {
+        if rot.is_cube_rotation() {
+            return match self {
+                Metric::Etm => 1,
+                _ => 0,
+            };
+        }
+
+        let quarters = if rot.is_double() { 2 } else { 1 };
+        match self {
+            Metric::Htm if rot.is_slice() => 2,
+            Metric::Htm => 1,
+            Metric::Qtm if rot.is_slice() => 2 * quarters,
+            Metric::Qtm => quarters,
+            Metric::Stm | Metric::Etm => 1,
+            Metric::Qstm => quarters,
+        }
+    }
+
+    /// Length of a sequence of rotations in this metric
+    pub fn count(&self, rotations: &[Rotation]) -> u32 {
+        rotations.iter().map(|&rot| self.rotation_cost(rot)).sum()
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metric = match self {
+            Metric::Htm => "HTM",
+            Metric::Qtm => "QTM",
+            Metric::Stm => "STM",
+            Metric::Etm => "ETM",
+            Metric::Qstm => "QSTM",
+        };
+        write!(f, "{}", metric)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Unknown metric: {0}")]
+pub struct UnknownMetric(String);
+
+impl FromStr for Metric {
+    type Err = UnknownMetric;
+
+    /// Case insensitive metric name, e.g. `htm` or `QSTM`
+    fn from_str(metric: &str) -> Result<Metric, Self::Err> {
+        match metric.to_ascii_uppercase().as_str() {
+            "HTM" => Ok(Metric::Htm),
+            "QTM" => Ok(Metric::Qtm),
+            "STM" => Ok(Metric::Stm),
+            "ETM" => Ok(Metric::Etm),
+            "QSTM" => Ok(Metric::Qstm),
+            _ => Err(UnknownMetric(metric.to_string())),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+/*
+Optimal solver, Korf's IDA* with pattern databases
+
+Searches for solutions with face turns of more and more moves, leaving a path as soon as a lower
+bound of the moves left says it can't be solved with the moves it has left. The bound is the
+largest exact distance of three parts of the cube, stored for every way of placing them in
+pattern databases: the corners, and two halves of the edges (6 edges each, with their
+orientation). A part can't be solved in fewer moves than the whole cube, so the bound never
+overestimates and the first length with solutions is the optimal one.
+
+The databases hold about 170 million distances, 4 bits each, built the first time the solver
+is used and then kept in the cache directory.
+*/
+
+use bon::builder;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use std::thread;
+
+use crate::coord::*;
+use crate::cube::CubeState;
+use crate::cubie::{CubieCube, SolveErr};
+use crate::rotation::Rotation;
+use crate::solution::{self, Solution};
+use crate::tables::{self, Table};
+
+/// Every cube can be solved in 20 moves
+const GODS_NUMBER: u8 = 20;
+
+const CORNER_STATES: usize = CORNER_PERMS * TWISTS;
+/// Ways of placing 6 edges in the 12 edge positions
+const EDGE6_PLACEMENTS: usize = 12 * 11 * 10 * 9 * 8 * 7;
+/// Placements of 6 edges, each of them flipped or not
+const EDGE6_STATES: usize = EDGE6_PLACEMENTS << 6;
+
+/// Version of the databases kept on disk, to change along with how they are built
+const TABLE_VERSION: u32 = 1;
+
+/// Distance of the pieces not visited yet while building a database
+const UNVISITED: u8 = 0xF;
+
+/// Fewest moves solving a state, and the solutions of that length
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OptimalSolutions {
+    /// Fewest face turns (HTM) solving the state
+    pub length: u8,
+    /// Solutions of that length, every one of them or only the first one found
+    pub solutions: Vec<Solution>,
+}
+
+/// Positions of the 6 edges starting at first, then which of them are flipped, bit i being the
+/// one of edge first + i
+fn edge6(cube: &CubieCube, first: u8) -> usize {
+    let mut positions = [0; 6];
+    let mut flips = 0;
+    for (pos, (&edge, &eo)) in cube.ep.iter().zip(&cube.eo).enumerate() {
+        if (first..first + 6).contains(&edge) {
+            positions[(edge - first) as usize] = pos as u8;
+            flips |= (eo as usize) << (edge - first);
+        }
+    }
+    placement(&positions, 12) << 6 | flips
+}
+
+/// Edge6 coordinate of every move from every placement with no edge flipped, which only depends
+/// on the positions and not on which 6 edges are placed
+fn edge6_move_table() -> Vec<[u32; 18]> {
+    (0..EDGE6_PLACEMENTS)
+        .map(|placement| {
+            let positions = set_placement(placement, 6, 12);
+            let mut cube = CubieCube::SOLVED;
+            let mut others = 6..12;
+            for pos in 0..12 {
+                cube.ep[pos as usize] = match positions.iter().position(|&it| it == pos) {
+                    Some(edge) => edge as u8,
+                    None => others.next().unwrap(),
+                };
+            }
+            FACE_TURNS.map(|rot| edge6(&cube.rotate(rot), 0) as u32)
+        })
+        .collect()
+}
+
+fn distance_at(distances: &[u8], idx: usize) -> u8 {
+    distances[idx / 2] >> (idx % 2 * 4) & 0xF
+}
+
+fn set_distance(distances: &mut [u8], idx: usize, distance: u8) {
+    let shift = idx % 2 * 4;
+    let byte = &mut distances[idx / 2];
+    *byte = *byte & !(0xF << shift) | distance << shift;
+}
+
+///
+/// Fewest moves solving each of the size states, by breadth first search from the solved one,
+/// packed two per byte
+///
+/// Once most states are visited, the ones left check whether one of their neighbors is at
+/// the current depth instead, every face turn being undone by another one
+///
+fn pattern_db(size: usize, solved: usize, neighbors: impl Fn(usize) -> [usize; 18]) -> Vec<u8> {
+    let mut distances = vec![0xFF; size.div_ceil(2)];
+    set_distance(&mut distances, solved, 0);
+
+    let mut depth = 0;
+    let mut filled = 1;
+    while filled < size {
+        let backward = filled > size / 2;
+        for idx in 0..size {
+            if backward {
+                if distance_at(&distances, idx) == UNVISITED
+                    && neighbors(idx)
+                        .iter()
+                        .any(|&next| distance_at(&distances, next) == depth)
+                {
+                    set_distance(&mut distances, idx, depth + 1);
+                    filled += 1;
+                }
+            } else if distance_at(&distances, idx) == depth {
+                for next in neighbors(idx) {
+                    if distance_at(&distances, next) == UNVISITED {
+                        set_distance(&mut distances, next, depth + 1);
+                        filled += 1;
+                    }
+                }
+            }
+        }
+        depth += 1;
+    }
+    distances
+}
+
+struct Databases {
+    corners: Table,
+    /// Edges UR to DF, and DL to BR
+    edges: [Table; 2],
+}
+
+static DATABASES: LazyLock<Databases> = LazyLock::new(|| {
+    let corners = tables::load_or_build("optimal_corners", TABLE_VERSION, || {
+        let corner_perm_moves = move_table(CORNER_PERMS, &FACE_TURNS, set_corner_perm, corner_perm);
+        let twist_moves = move_table(TWISTS, &FACE_TURNS, set_twist, twist);
+        pattern_db(CORNER_STATES, 0, |idx| {
+            let (perm, twist) = (idx / TWISTS, idx % TWISTS);
+            std::array::from_fn(|m| {
+                corner_perm_moves[perm][m] as usize * TWISTS + twist_moves[twist][m] as usize
+            })
+        })
+    });
+
+    // only needed when one of the edge databases isn't on disk
+    let edge6_moves = LazyLock::new(edge6_move_table);
+    let edges = [(0, "optimal_edges_ur_df"), (6, "optimal_edges_dl_br")].map(|(first, name)| {
+        tables::load_or_build(name, TABLE_VERSION, || {
+            let solved = edge6(&CubieCube::SOLVED, first);
+            pattern_db(EDGE6_STATES, solved, |idx| {
+                let moves = &edge6_moves[idx >> 6];
+                // the flips follow the edges, so the ones of the move just add up
+                moves.map(|next| next as usize ^ (idx & 0b11_1111))
+            })
+        })
+    });
+
+    Databases { corners, edges }
+});
+
+impl Databases {
+    /// Fewest moves the cube can be solved in according to the databases
+    fn distance(&self, cube: &CubieCube) -> u8 {
+        let corners = distance_at(&self.corners, corner_perm(cube) * TWISTS + twist(cube));
+        let edges =
+            (0..2).map(|group| distance_at(&self.edges[group], edge6(cube, group as u8 * 6)));
+        edges.fold(corners, u8::max)
+    }
+
+    /// Searches the solutions of exactly depth more moves after the path, stopping after the
+    /// first one unless looking for all of them
+    fn search(
+        &self,
+        cube: &CubieCube,
+        depth: u8,
+        all_solutions: bool,
+        stop: &AtomicBool,
+        path: &mut Vec<Rotation>,
+        solutions: &mut Vec<Solution>,
+    ) {
+        if depth == 0 {
+            // only the solved cube has a distance of 0
+            solutions.push(path.clone().into());
+            if !all_solutions {
+                stop.store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        for rot in FACE_TURNS {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            // other orders of moves on the same axis are different solutions, only
+            // needed when looking for all of them
+            let redundant = if all_solutions {
+                solution::is_rot_useless(path, rot)
+            } else {
+                solution::is_rot_redundant(path, rot)
+            };
+            if redundant {
+                continue;
+            }
+
+            let next = cube.rotate(rot);
+            if self.distance(&next) >= depth {
+                continue;
+            }
+
+            path.push(rot);
+            self.search(&next, depth - 1, all_solutions, stop, path, solutions);
+            path.pop();
+        }
+    }
+
+    /// Searches the solutions of exactly depth moves, each first move in its own thread
+    fn search_threaded(&self, cube: &CubieCube, depth: u8, all_solutions: bool) -> Vec<Solution> {
+        let stop = AtomicBool::new(false);
+        let mut solutions: Vec<Solution> = thread::scope(|scope| {
+            let handles: Vec<_> = FACE_TURNS
+                .map(|rot| {
+                    let stop = &stop;
+                    scope.spawn(move || {
+                        let next = cube.rotate(rot);
+                        let mut solutions = Vec::new();
+                        if self.distance(&next) < depth {
+                            let mut path = vec![rot];
+                            self.search(
+                                &next,
+                                depth - 1,
+                                all_solutions,
+                                stop,
+                                &mut path,
+                                &mut solutions,
+                            );
+                        }
+                        solutions
+                    })
+                })
+                .into_iter()
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        // threads finding a solution at about the same time can each return one
+        if !all_solutions {
+            solutions.truncate(1);
+        }
+        solutions
+    }
+}
+
+///
+/// Finds the fewest face turns (HTM) solving a fully specified state, and a solution of that
+/// length, or every one of them with all_solutions
+///
+/// Most states take 16 to 18 moves, which takes from minutes to hours, and exponentially less
+/// time for each move less. max_length gives up on longer solutions. With multi_threaded, the
+/// search is split between threads by its first move
+///
+/// The pattern databases are built the first time the solver is used, which takes several
+/// seconds in release builds, and kept in the cache directory for the next runs
+///
+#[builder]
+pub fn solve(
+    state: CubeState,
+    #[builder(default)] all_solutions: bool,
+    #[builder(default = GODS_NUMBER)] max_length: u8,
+    #[builder(default)] multi_threaded: bool,
+) -> Result<OptimalSolutions, SolveErr> {
+    let cube = CubieCube::solvable(state)?;
+    let normalized = cube.normalized();
+    let databases = &*DATABASES;
+
+    for length in databases.distance(&normalized)..=max_length {
+        let solutions = if multi_threaded && length > 0 {
+            databases.search_threaded(&normalized, length, all_solutions)
+        } else {
+            let mut solutions = Vec::new();
+            let stop = AtomicBool::new(false);
+            databases.search(
+                &normalized,
+                length,
+                all_solutions,
+                &stop,
+                &mut Vec::new(),
+                &mut solutions,
+            );
+            solutions
+        };
+
+        if !solutions.is_empty() {
+            let solutions = solutions
+                .into_iter()
+                .map(|solution| {
+                    let held: Vec<Rotation> =
+                        solution.iter().map(|&rot| cube.turn_as_held(rot)).collect();
+                    held.into()
+                })
+                .collect();
+            return Ok(OptimalSolutions { length, solutions });
+        }
+    }
+    Err(SolveErr::NotFound(max_length))
+}
@@ -0,0 +1,246 @@
+use thiserror::Error;
+
+use crate::algorithm::Algorithm;
+use crate::rotation::Rotation;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseErr {
+    #[error("Unexpected character '{0}' at position {1}")]
+    UnexpectedChar(char, usize),
+    #[error("Unexpected end of algorithm at position {0}")]
+    UnexpectedEnd(usize),
+    #[error("Unclosed '{0}' at position {1}")]
+    Unclosed(char, usize),
+    #[error("Invalid amount of turns at position {0}")]
+    InvalidTurns(usize),
+    #[error("Invalid repetition count at position {0}")]
+    InvalidCount(usize),
+    #[error("Empty move at position {0}")]
+    EmptyMove(usize),
+}
+
+/// Most moves a repeated group can expand to, far more than any algorithm needs, so that
+/// counts like (R U)4000000000 are errors instead of running out of memory
+const MAX_EXPANDED_LEN: usize = 10_000;
+
+/// Recursive descent parser over the characters of an algorithm
+///
+/// sequence    := item*
+/// item        := (move | group | bracket) [count] [']
+/// group       := '(' sequence ')'
+/// bracket     := '[' sequence (',' | ':') sequence ']'
+/// move        := letter ['w'] [count] [']
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(alg: &str) -> Self {
+        Parser {
+            chars: alg.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char, opened: (char, usize)) -> Result<(), ParseErr> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(ParseErr::UnexpectedChar(c, self.pos)),
+            None => Err(ParseErr::Unclosed(opened.0, opened.1)),
+        }
+    }
+
+    /// Parses digits right after the previous token, without skipping whitespace
+    fn count(&mut self) -> Result<Option<u32>, ParseErr> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        if start == self.pos {
+            return Ok(None);
+        }
+
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .map(Some)
+            .map_err(|_| ParseErr::InvalidCount(start))
+    }
+
+    /// Parses a prime right after the previous token, without skipping whitespace
+    fn prime(&mut self) -> bool {
+        if self.chars.get(self.pos) == Some(&'\'') {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Rotation>, ParseErr> {
+        let mut result = Vec::new();
+        while let Some(c) = self.peek() {
+            if matches!(c, ')' | ']' | ',' | ':') {
+                break;
+            }
+            result.extend(self.item()?);
+        }
+        Ok(result)
+    }
+
+    fn item(&mut self) -> Result<Vec<Rotation>, ParseErr> {
+        let start = self.pos;
+        let seq = match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let seq = self.sequence()?;
+                self.expect(')', ('(', start))?;
+                seq
+            }
+            Some('[') => {
+                self.pos += 1;
+                let a = self.sequence()?;
+                let separator_pos = self.pos;
+                let separator = self.peek();
+                if !matches!(separator, Some(',') | Some(':')) {
+                    return match separator {
+                        Some(c) => Err(ParseErr::UnexpectedChar(c, separator_pos)),
+                        None => Err(ParseErr::Unclosed('[', start)),
+                    };
+                }
+                self.pos += 1;
+                let b = self.sequence()?;
+                self.expect(']', ('[', start))?;
+
+                let mut seq = a.clone();
+                seq.extend(&b);
+                seq.extend(inverse(&a));
+                if separator == Some(',') {
+                    seq.extend(inverse(&b));
+                }
+                seq
+            }
+            Some(_) => return self.single_move().map(|rot| rot.into_iter().collect()),
+            None => return Err(ParseErr::UnexpectedEnd(self.pos)),
+        };
+
+        let count_pos = self.pos;
+        let count = self.count()?.unwrap_or(1);
+        let len = seq
+            .len()
+            .checked_mul(count as usize)
+            .filter(|&len| count > 0 && len <= MAX_EXPANDED_LEN)
+            .ok_or(ParseErr::InvalidCount(count_pos))?;
+        let seq = if self.prime() { inverse(&seq) } else { seq };
+
+        Ok(seq.iter().copied().cycle().take(len).collect())
+    }
+
+    /// Returns None for moves that do a multiple of a full turn, like R4
+    fn single_move(&mut self) -> Result<Option<Rotation>, ParseErr> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let letter = match self.chars.get(self.pos) {
+            Some(&c) => c,
+            None => return Err(ParseErr::UnexpectedEnd(self.pos)),
+        };
+        self.pos += 1;
+
+        if !"ULFRBDMES".contains(letter) && !"ulfrbdxyz".contains(letter) {
+            return Err(ParseErr::UnexpectedChar(letter, start));
+        }
+        let face = letter.to_ascii_uppercase();
+        let mut wide = "ulfrbd".contains(letter);
+
+        if !wide && "ULFRBD".contains(letter) && self.chars.get(self.pos) == Some(&'w') {
+            self.pos += 1;
+            wide = true;
+        }
+
+        let count_pos = self.pos;
+        let turns = self.count()?.unwrap_or(1);
+        if turns == 0 {
+            return Err(ParseErr::InvalidTurns(count_pos));
+        }
+        let turns = if self.prime() {
+            (4 - turns % 4) % 4
+        } else {
+            turns % 4
+        };
+
+        if turns == 0 {
+            return Ok(None);
+        }
+
+        let [cw, double, ccw] = match (face, wide) {
+            ('U', false) => [Rotation::U, Rotation::U2, Rotation::Up],
+            ('L', false) => [Rotation::L, Rotation::L2, Rotation::Lp],
+            ('F', false) => [Rotation::F, Rotation::F2, Rotation::Fp],
+            ('R', false) => [Rotation::R, Rotation::R2, Rotation::Rp],
+            ('B', false) => [Rotation::B, Rotation::B2, Rotation::Bp],
+            ('D', false) => [Rotation::D, Rotation::D2, Rotation::Dp],
+            ('U', true) => [Rotation::Uw, Rotation::Uw2, Rotation::Uwp],
+            ('L', true) => [Rotation::Lw, Rotation::Lw2, Rotation::Lwp],
+            ('F', true) => [Rotation::Fw, Rotation::Fw2, Rotation::Fwp],
+            ('R', true) => [Rotation::Rw, Rotation::Rw2, Rotation::Rwp],
+            ('B', true) => [Rotation::Bw, Rotation::Bw2, Rotation::Bwp],
+            ('D', true) => [Rotation::Dw, Rotation::Dw2, Rotation::Dwp],
+            ('M', _) => [Rotation::M, Rotation::M2, Rotation::Mp],
+            ('E', _) => [Rotation::E, Rotation::E2, Rotation::Ep],
+            ('S', _) => [Rotation::S, Rotation::S2, Rotation::Sp],
+            ('X', _) => [Rotation::X, Rotation::X2, Rotation::Xp],
+            ('Y', _) => [Rotation::Y, Rotation::Y2, Rotation::Yp],
+            ('Z', _) => [Rotation::Z, Rotation::Z2, Rotation::Zp],
+            _ => unreachable!(),
+        };
+
+        Ok(Some([cw, double, ccw][turns as usize - 1]))
+    }
+}
+
+fn inverse(seq: &[Rotation]) -> Vec<Rotation> {
+    seq.iter().rev().map(|rot| rot.reverse()).collect()
+}
+
+///
+/// Parses an algorithm written in the usual notation, e.g. `R U R' U'`, `RUR'U'`,
+/// `(R U R' U')3`, `[R, U]` (commutator), `[F: R U R']` (conjugate) or `(R U)'` (inverse)
+///
+/// Wide moves can be written both as `r` and as `Rw`
+///
+pub fn parse_algorithm(alg: &str) -> Result<Algorithm, ParseErr> {
+    let mut parser = Parser::new(alg);
+    let result = parser.sequence()?;
+
+    match parser.peek() {
+        Some(c) => Err(ParseErr::UnexpectedChar(c, parser.pos)),
+        None => Ok(result.into()),
+    }
+}
+
+/// Parses a single move, returns None if it does a multiple of a full turn
+pub(crate) fn parse_move(rot: &str) -> Result<Option<Rotation>, ParseErr> {
+    let mut parser = Parser::new(rot);
+    let result = parser.single_move()?;
+
+    match parser.peek() {
+        Some(c) => Err(ParseErr::UnexpectedChar(c, parser.pos)),
+        None => Ok(result),
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::cube::{self, CubeState, FromStrErr, DISPLAYIDX_TO_CELLIDX};
+use crate::geometry::{self, CELL_COUNT};
+use crate::rotation::Rotation;
+
+#[derive(Debug, Error)]
+pub enum PatternFromStrErr {
+    #[error(transparent)]
+    State(#[from] FromStrErr),
+    #[error("Unclosed color set")]
+    UnclosedSet,
+    #[error("Empty color set")]
+    EmptySet,
+}
+
+/// Bit of every color a sticker can have, bit 0 being unspecified (N)
+const ANY: u8 = 0b111_1111;
+
+/// Bits of the six colors, without unspecified (N)
+const COLORS: u8 = 0b111_1110;
+
+///
+/// A state where every sticker can be any color out of a set, e.g. to only care about
+/// the white and yellow stickers being on the U and D faces
+///
+/// Written like a state, with a set of colors in brackets where a single one would go,
+/// [WY] being white or yellow and [^Y] any color but yellow
+///
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CubePattern {
+    /// Colors each cell can have, color c being bit c
+    colors: [u8; CELL_COUNT],
+}
+
+impl CubePattern {
+    /// Whether every sticker of the state has one of the colors its cell can have,
+    /// the unspecified stickers of the state only match unspecified ones
+    pub fn accepts(&self, state: &CubeState) -> bool {
+        (0..CELL_COUNT).all(|idx| self.colors[idx] & (1 << state.cell(idx as u8)) != 0)
+    }
+
+    /// Colors the cell can have, color c being bit c and unspecified (N) bit 0
+    pub(crate) fn cell_colors(&self, idx: usize) -> u8 {
+        self.colors[idx]
+    }
+
+    /// Returns the state with the cells that can only have one color, leaving the rest
+    /// unspecified, which every state accepted by the pattern matches
+    pub fn relaxed(&self) -> CubeState {
+        let cells = self.colors.map(|colors| {
+            if colors.count_ones() == 1 {
+                colors.trailing_zeros() as u8
+            } else {
+                0
+            }
+        });
+        CubeState::from_cells(&cells)
+    }
+
+    pub fn rotate(self, rotation: Rotation) -> Self {
+        let permutation = &geometry::PERMUTATIONS[rotation as usize];
+        let mut colors = [ANY; CELL_COUNT];
+        for (cell, &dest) in permutation.iter().enumerate() {
+            colors[dest as usize] = self.colors[cell];
+        }
+        CubePattern { colors }
+    }
+
+    /// Returns the 24 ways of holding this pattern, starting with the current one
+    pub fn orientations(self) -> impl Iterator<Item = CubePattern> {
+        cube::orientation_rotations().map(move |rotations| {
+            rotations
+                .into_iter()
+                .flatten()
+                .fold(self, |pattern, rot| pattern.rotate(rot))
+        })
+    }
+
+    /// Returns the orientation of this pattern with the same centers as the other state
+    pub fn oriented_like(self, other: &CubeState) -> Option<Self> {
+        let other = CubePattern::from(*other);
+        self.orientations().find(|pattern| {
+            (geometry::CENTER_START..geometry::CENTER_START + 6)
+                .all(|idx| pattern.colors[idx] == other.colors[idx])
+        })
+    }
+}
+
+/// A state accepts exactly itself with its unspecified stickers filled in
+impl From<CubeState> for CubePattern {
+    fn from(state: CubeState) -> Self {
+        let mut colors = [ANY; CELL_COUNT];
+        for (idx, colors) in colors.iter_mut().enumerate() {
+            let cell = state.cell(idx as u8);
+            if cell != 0 {
+                *colors = 1 << cell;
+            }
+        }
+        CubePattern { colors }
+    }
+}
+
+/// Reads the colors inside brackets, after the opening one
+fn parse_set(chars: &mut impl Iterator<Item = char>) -> Result<u8, PatternFromStrErr> {
+    let mut set = String::new();
+    loop {
+        match chars.next() {
+            Some(']') => break,
+            Some(color) => set.push(color),
+            None => return Err(PatternFromStrErr::UnclosedSet),
+        }
+    }
+
+    let (negated, set) = match set.strip_prefix('^') {
+        Some(set) => (true, set),
+        None => (false, set.as_str()),
+    };
+    let colors = set
+        .chars()
+        .try_fold(0, |colors, color| match cube::char_to_cell(color) {
+            Some(cell) if cell != 0 => Ok(colors | 1 << cell),
+            _ => Err(FromStrErr::InvalidChar(color)),
+        })?;
+
+    let colors = if negated { COLORS & !colors } else { colors };
+    if colors == 0 {
+        return Err(PatternFromStrErr::EmptySet);
+    }
+    Ok(colors)
+}
+
+impl FromStr for CubePattern {
+    type Err = PatternFromStrErr;
+
+    fn from_str(pattern_str: &str) -> Result<CubePattern, Self::Err> {
+        let mut sets = Vec::with_capacity(DISPLAYIDX_TO_CELLIDX.len());
+        let mut chars = pattern_str.chars();
+        while let Some(color) = chars.next() {
+            let set = match color {
+                '[' => parse_set(&mut chars)?,
+                'N' => ANY,
+                _ => 1 << cube::char_to_cell(color).ok_or(FromStrErr::InvalidChar(color))?,
+            };
+            sets.push(set);
+        }
+
+        if sets.len() != DISPLAYIDX_TO_CELLIDX.len() {
+            return Err(FromStrErr::InvalidLen.into());
+        }
+
+        let mut colors = [ANY; CELL_COUNT];
+        for (&cellidx, set) in DISPLAYIDX_TO_CELLIDX.iter().zip(sets) {
+            colors[cellidx] = set;
+        }
+        Ok(CubePattern { colors })
+    }
+}
+
+/// Written in the same format it is read from
+impl fmt::Display for CubePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &cellidx in &DISPLAYIDX_TO_CELLIDX {
+            let colors = self.colors[cellidx];
+            if colors == ANY {
+                write!(f, "N")?;
+            } else if colors.count_ones() == 1 {
+                write!(f, "{}", cube::cell_to_char(colors.trailing_zeros() as u8))?;
+            } else {
+                write!(f, "[")?;
+                for cell in (1..7).filter(|cell| colors & (1 << cell) != 0) {
+                    write!(f, "{}", cube::cell_to_char(cell))?;
+                }
+                write!(f, "]")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,22 +1,69 @@
 use std::fmt;
-use strum_macros::EnumIter;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+use strum_macros::{EnumCount, EnumIter, FromRepr};
 
-use crate::face::Face;
+use crate::face::{Axis, Face};
+use crate::parser::{self, ParseErr};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, EnumIter, Debug, PartialOrd, Ord)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumCount, FromRepr, Debug, PartialOrd, Ord,
+)]
 pub enum Rotation {
     U,
     Up,
+    U2,
     L,
     Lp,
+    L2,
     F,
     Fp,
+    F2,
     R,
     Rp,
+    R2,
     B,
     Bp,
+    B2,
     D,
     Dp,
+    D2,
+    M,
+    Mp,
+    M2,
+    E,
+    Ep,
+    E2,
+    S,
+    Sp,
+    S2,
+    Uw,
+    Uwp,
+    Uw2,
+    Lw,
+    Lwp,
+    Lw2,
+    Fw,
+    Fwp,
+    Fw2,
+    Rw,
+    Rwp,
+    Rw2,
+    Bw,
+    Bwp,
+    Bw2,
+    Dw,
+    Dwp,
+    Dw2,
+    X,
+    Xp,
+    X2,
+    Y,
+    Yp,
+    Y2,
+    Z,
+    Zp,
+    Z2,
 }
 
 impl fmt::Display for Rotation {
@@ -24,21 +71,79 @@ impl fmt::Display for Rotation {
         let rot = match self {
             Rotation::U => "U",
             Rotation::Up => "U'",
+            Rotation::U2 => "U2",
             Rotation::L => "L",
             Rotation::Lp => "L'",
+            Rotation::L2 => "L2",
             Rotation::F => "F",
             Rotation::Fp => "F'",
+            Rotation::F2 => "F2",
             Rotation::R => "R",
             Rotation::Rp => "R'",
+            Rotation::R2 => "R2",
             Rotation::B => "B",
             Rotation::Bp => "B'",
+            Rotation::B2 => "B2",
             Rotation::D => "D",
             Rotation::Dp => "D'",
+            Rotation::D2 => "D2",
+            Rotation::M => "M",
+            Rotation::Mp => "M'",
+            Rotation::M2 => "M2",
+            Rotation::E => "E",
+            Rotation::Ep => "E'",
+            Rotation::E2 => "E2",
+            Rotation::S => "S",
+            Rotation::Sp => "S'",
+            Rotation::S2 => "S2",
+            Rotation::Uw => "u",
+            Rotation::Uwp => "u'",
+            Rotation::Uw2 => "u2",
+            Rotation::Lw => "l",
+            Rotation::Lwp => "l'",
+            Rotation::Lw2 => "l2",
+            Rotation::Fw => "f",
+            Rotation::Fwp => "f'",
+            Rotation::Fw2 => "f2",
+            Rotation::Rw => "r",
+            Rotation::Rwp => "r'",
+            Rotation::Rw2 => "r2",
+            Rotation::Bw => "b",
+            Rotation::Bwp => "b'",
+            Rotation::Bw2 => "b2",
+            Rotation::Dw => "d",
+            Rotation::Dwp => "d'",
+            Rotation::Dw2 => "d2",
+            Rotation::X => "x",
+            Rotation::Xp => "x'",
+            Rotation::X2 => "x2",
+            Rotation::Y => "y",
+            Rotation::Yp => "y'",
+            Rotation::Y2 => "y2",
+            Rotation::Z => "z",
+            Rotation::Zp => "z'",
+            Rotation::Z2 => "z2",
         };
+
+        // wide moves are written as r by default and as Rw with the alternate flag ({:#})
+        if f.alternate() && self.is_wide() {
+            let mut chars = rot.chars();
+            let face = chars.next().unwrap().to_ascii_uppercase();
+            return write!(f, "{}w{}", face, chars.as_str());
+        }
+
         write!(f, "{}", rot)
     }
 }
 
+impl FromStr for Rotation {
+    type Err = ParseErr;
+
+    fn from_str(rot: &str) -> Result<Rotation, Self::Err> {
+        parser::parse_move(rot)?.ok_or(ParseErr::EmptyMove(0))
+    }
+}
+
 impl Rotation {
     pub fn reverse(&self) -> Rotation {
         match self {
@@ -54,47 +159,301 @@ impl Rotation {
             Rotation::Bp => Rotation::B,
             Rotation::D => Rotation::Dp,
             Rotation::Dp => Rotation::D,
+            Rotation::M => Rotation::Mp,
+            Rotation::Mp => Rotation::M,
+            Rotation::E => Rotation::Ep,
+            Rotation::Ep => Rotation::E,
+            Rotation::S => Rotation::Sp,
+            Rotation::Sp => Rotation::S,
+            Rotation::Uw => Rotation::Uwp,
+            Rotation::Uwp => Rotation::Uw,
+            Rotation::Lw => Rotation::Lwp,
+            Rotation::Lwp => Rotation::Lw,
+            Rotation::Fw => Rotation::Fwp,
+            Rotation::Fwp => Rotation::Fw,
+            Rotation::Rw => Rotation::Rwp,
+            Rotation::Rwp => Rotation::Rw,
+            Rotation::Bw => Rotation::Bwp,
+            Rotation::Bwp => Rotation::Bw,
+            Rotation::Dw => Rotation::Dwp,
+            Rotation::Dwp => Rotation::Dw,
+            Rotation::X => Rotation::Xp,
+            Rotation::Xp => Rotation::X,
+            Rotation::Y => Rotation::Yp,
+            Rotation::Yp => Rotation::Y,
+            Rotation::Z => Rotation::Zp,
+            Rotation::Zp => Rotation::Z,
+            Rotation::U2
+            | Rotation::L2
+            | Rotation::F2
+            | Rotation::R2
+            | Rotation::B2
+            | Rotation::D2
+            | Rotation::M2
+            | Rotation::E2
+            | Rotation::S2
+            | Rotation::Uw2
+            | Rotation::Lw2
+            | Rotation::Fw2
+            | Rotation::Rw2
+            | Rotation::Bw2
+            | Rotation::Dw2
+            | Rotation::X2
+            | Rotation::Y2
+            | Rotation::Z2 => *self,
+        }
+    }
+
+    /// Returns the face opposite to the one being turned, None for slices and cube rotations
+    pub fn opposite_face(&self) -> Option<Face> {
+        self.face().map(|face| match face {
+            Face::U => Face::D,
+            Face::L => Face::R,
+            Face::F => Face::B,
+            Face::R => Face::L,
+            Face::B => Face::F,
+            Face::D => Face::U,
+        })
+    }
+
+    /// Returns the face being turned, None for slices and cube rotations
+    pub fn face(&self) -> Option<Face> {
+        match self {
+            Rotation::U
+            | Rotation::Up
+            | Rotation::U2
+            | Rotation::Uw
+            | Rotation::Uwp
+            | Rotation::Uw2 => Some(Face::U),
+            Rotation::L
+            | Rotation::Lp
+            | Rotation::L2
+            | Rotation::Lw
+            | Rotation::Lwp
+            | Rotation::Lw2 => Some(Face::L),
+            Rotation::F
+            | Rotation::Fp
+            | Rotation::F2
+            | Rotation::Fw
+            | Rotation::Fwp
+            | Rotation::Fw2 => Some(Face::F),
+            Rotation::R
+            | Rotation::Rp
+            | Rotation::R2
+            | Rotation::Rw
+            | Rotation::Rwp
+            | Rotation::Rw2 => Some(Face::R),
+            Rotation::B
+            | Rotation::Bp
+            | Rotation::B2
+            | Rotation::Bw
+            | Rotation::Bwp
+            | Rotation::Bw2 => Some(Face::B),
+            Rotation::D
+            | Rotation::Dp
+            | Rotation::D2
+            | Rotation::Dw
+            | Rotation::Dwp
+            | Rotation::Dw2 => Some(Face::D),
+            Rotation::M
+            | Rotation::Mp
+            | Rotation::M2
+            | Rotation::E
+            | Rotation::Ep
+            | Rotation::E2
+            | Rotation::S
+            | Rotation::Sp
+            | Rotation::S2
+            | Rotation::X
+            | Rotation::Xp
+            | Rotation::X2
+            | Rotation::Y
+            | Rotation::Yp
+            | Rotation::Y2
+            | Rotation::Z
+            | Rotation::Zp
+            | Rotation::Z2 => None,
         }
     }
 
-    pub fn opposite_face(&self) -> Face {
+    pub const fn axis(&self) -> Axis {
         match self {
-            Rotation::U => Face::D,
-            Rotation::Up => Face::D,
-            Rotation::L => Face::R,
-            Rotation::Lp => Face::R,
-            Rotation::F => Face::B,
-            Rotation::Fp => Face::B,
-            Rotation::R => Face::L,
-            Rotation::Rp => Face::L,
-            Rotation::B => Face::F,
-            Rotation::Bp => Face::F,
-            Rotation::D => Face::U,
-            Rotation::Dp => Face::U,
+            Rotation::L
+            | Rotation::Lp
+            | Rotation::L2
+            | Rotation::R
+            | Rotation::Rp
+            | Rotation::R2
+            | Rotation::M
+            | Rotation::Mp
+            | Rotation::M2
+            | Rotation::Lw
+            | Rotation::Lwp
+            | Rotation::Lw2
+            | Rotation::Rw
+            | Rotation::Rwp
+            | Rotation::Rw2
+            | Rotation::X
+            | Rotation::Xp
+            | Rotation::X2 => Axis::X,
+            Rotation::U
+            | Rotation::Up
+            | Rotation::U2
+            | Rotation::D
+            | Rotation::Dp
+            | Rotation::D2
+            | Rotation::E
+            | Rotation::Ep
+            | Rotation::E2
+            | Rotation::Uw
+            | Rotation::Uwp
+            | Rotation::Uw2
+            | Rotation::Dw
+            | Rotation::Dwp
+            | Rotation::Dw2
+            | Rotation::Y
+            | Rotation::Yp
+            | Rotation::Y2 => Axis::Y,
+            Rotation::F
+            | Rotation::Fp
+            | Rotation::F2
+            | Rotation::B
+            | Rotation::Bp
+            | Rotation::B2
+            | Rotation::S
+            | Rotation::Sp
+            | Rotation::S2
+            | Rotation::Fw
+            | Rotation::Fwp
+            | Rotation::Fw2
+            | Rotation::Bw
+            | Rotation::Bwp
+            | Rotation::Bw2
+            | Rotation::Z
+            | Rotation::Zp
+            | Rotation::Z2 => Axis::Z,
         }
     }
 
-    pub fn face(&self) -> Face {
+    /// Bitmask of the layers turned along the axis,
+    /// bit 0 being the L/D/B layer, bit 1 the middle one and bit 2 the R/U/F layer
+    pub const fn layers(&self) -> u8 {
         match self {
-            Rotation::U => Face::U,
-            Rotation::Up => Face::U,
-            Rotation::L => Face::L,
-            Rotation::Lp => Face::L,
-            Rotation::F => Face::F,
-            Rotation::Fp => Face::F,
-            Rotation::R => Face::R,
-            Rotation::Rp => Face::R,
-            Rotation::B => Face::B,
-            Rotation::Bp => Face::B,
-            Rotation::D => Face::D,
-            Rotation::Dp => Face::D,
+            Rotation::L | Rotation::Lp | Rotation::L2 => 0b001,
+            Rotation::D | Rotation::Dp | Rotation::D2 => 0b001,
+            Rotation::B | Rotation::Bp | Rotation::B2 => 0b001,
+            Rotation::M | Rotation::Mp | Rotation::M2 => 0b010,
+            Rotation::E | Rotation::Ep | Rotation::E2 => 0b010,
+            Rotation::S | Rotation::Sp | Rotation::S2 => 0b010,
+            Rotation::R | Rotation::Rp | Rotation::R2 => 0b100,
+            Rotation::U | Rotation::Up | Rotation::U2 => 0b100,
+            Rotation::F | Rotation::Fp | Rotation::F2 => 0b100,
+            Rotation::Lw | Rotation::Lwp | Rotation::Lw2 => 0b011,
+            Rotation::Dw | Rotation::Dwp | Rotation::Dw2 => 0b011,
+            Rotation::Bw | Rotation::Bwp | Rotation::Bw2 => 0b011,
+            Rotation::Rw | Rotation::Rwp | Rotation::Rw2 => 0b110,
+            Rotation::Uw | Rotation::Uwp | Rotation::Uw2 => 0b110,
+            Rotation::Fw | Rotation::Fwp | Rotation::Fw2 => 0b110,
+            Rotation::X | Rotation::Xp | Rotation::X2 => 0b111,
+            Rotation::Y | Rotation::Yp | Rotation::Y2 => 0b111,
+            Rotation::Z | Rotation::Zp | Rotation::Z2 => 0b111,
         }
     }
 
+    /// Quarter turns done to the layers, clockwise looking from the R/U/F side of the axis
+    pub const fn turns(&self) -> i8 {
+        match self {
+            Rotation::U | Rotation::R | Rotation::F | Rotation::S => 1,
+            Rotation::Uw | Rotation::Rw | Rotation::Fw => 1,
+            Rotation::X | Rotation::Y | Rotation::Z => 1,
+            Rotation::Xp | Rotation::Yp | Rotation::Zp => -1,
+            Rotation::Up | Rotation::Rp | Rotation::Fp | Rotation::Sp => -1,
+            Rotation::Uwp | Rotation::Rwp | Rotation::Fwp => -1,
+            Rotation::L | Rotation::D | Rotation::B | Rotation::M | Rotation::E => -1,
+            Rotation::Lw | Rotation::Dw | Rotation::Bw => -1,
+            Rotation::Lp | Rotation::Dp | Rotation::Bp | Rotation::Mp | Rotation::Ep => 1,
+            Rotation::Lwp | Rotation::Dwp | Rotation::Bwp => 1,
+            Rotation::U2
+            | Rotation::L2
+            | Rotation::F2
+            | Rotation::R2
+            | Rotation::B2
+            | Rotation::D2
+            | Rotation::M2
+            | Rotation::E2
+            | Rotation::S2
+            | Rotation::Uw2
+            | Rotation::Lw2
+            | Rotation::Fw2
+            | Rotation::Rw2
+            | Rotation::Bw2
+            | Rotation::Dw2
+            | Rotation::X2
+            | Rotation::Y2
+            | Rotation::Z2 => 2,
+        }
+    }
+
+    /// Returns the rotation turning the given layers of the axis, None if there isn't one
+    pub fn from_layers(axis: Axis, layers: u8, turns: i8) -> Option<Rotation> {
+        let turns = turns.rem_euclid(4);
+        Rotation::iter().find(|rot| {
+            rot.axis() == axis && rot.layers() == layers && rot.turns().rem_euclid(4) == turns
+        })
+    }
+
+    /// Returns the rotation seen in a mirror perpendicular to the axis,
+    /// e.g. mirroring R along the X axis gives L'
+    pub fn mirror(&self, axis: Axis) -> Rotation {
+        let (layers, turns) = if self.axis() == axis {
+            let layers = self.layers();
+            let reversed = (layers & 0b001) << 2 | (layers & 0b010) | (layers & 0b100) >> 2;
+            (reversed, self.turns())
+        } else {
+            (self.layers(), -self.turns())
+        };
+
+        Rotation::from_layers(self.axis(), layers, turns).unwrap()
+    }
+
     pub fn is_prime(&self) -> bool {
         matches!(
             self,
-            Rotation::Up | Rotation::Lp | Rotation::Fp | Rotation::Rp | Rotation::Bp | Rotation::Dp
+            Rotation::Up
+                | Rotation::Lp
+                | Rotation::Fp
+                | Rotation::Rp
+                | Rotation::Bp
+                | Rotation::Dp
+                | Rotation::Mp
+                | Rotation::Ep
+                | Rotation::Sp
+                | Rotation::Uwp
+                | Rotation::Lwp
+                | Rotation::Fwp
+                | Rotation::Rwp
+                | Rotation::Bwp
+                | Rotation::Dwp
+                | Rotation::Xp
+                | Rotation::Yp
+                | Rotation::Zp
         )
     }
+
+    pub fn is_double(&self) -> bool {
+        self.turns() == 2
+    }
+
+    pub fn is_slice(&self) -> bool {
+        self.layers() == 0b010
+    }
+
+    pub fn is_wide(&self) -> bool {
+        self.layers().count_ones() == 2
+    }
+
+    /// Whether this is a whole cube rotation (x, y or z) rather than a turn
+    pub fn is_cube_rotation(&self) -> bool {
+        self.layers() == 0b111
+    }
 }
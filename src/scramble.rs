@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use strum::IntoEnumIterator;
+use thiserror::Error;
+
+use crate::algorithm::Algorithm;
+use crate::cubie::CubieCube;
+use crate::parser::ParseErr;
+use crate::rotation::Rotation;
+use crate::solution;
+use crate::two_phase;
+
+/// Random number generator of the scrambles, seeded with the given seed or randomly
+/// when there is none
+pub fn rng(seed: Option<u64>) -> ChaCha8Rng {
+    match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    }
+}
+
+fn solve(cube: &CubieCube) -> Algorithm {
+    two_phase::solve_cubie(cube, two_phase::DEFAULT_MAX_LENGTH)
+        .expect("Every cube can be solved in at most 20 moves")
+}
+
+/// Returns a cube picked uniformly out of every cube that can be solved
+pub fn random_cube<R: Rng + ?Sized>(rng: &mut R) -> CubieCube {
+    let mut cube = CubieCube::SOLVED;
+    cube.cp.shuffle(rng);
+    cube.ep.shuffle(rng);
+    // swapping two edges pairs every edge permutation with the wrong parity with exactly
+    // one with the right parity, so the result stays uniform
+    if cube.corner_parity() != cube.edge_parity() {
+        cube.ep.swap(0, 1);
+    }
+
+    for co in &mut cube.co[..7] {
+        *co = rng.gen_range(0..3);
+    }
+    cube.co[7] = (3 - cube.co[..7].iter().sum::<u8>() % 3) % 3;
+    for eo in &mut cube.eo[..11] {
+        *eo = rng.gen_range(0..2);
+    }
+    cube.eo[11] = cube.eo[..11].iter().sum::<u8>() % 2;
+
+    cube
+}
+
+///
+/// Returns a random-state scramble, like the ones used in competitions: the solved cube is
+/// scrambled into a uniformly random state, so every state is as likely
+///
+/// The same seed always gives the same scramble
+///
+pub fn random_state(seed: Option<u64>) -> Algorithm {
+    random_state_with(&mut rng(seed))
+}
+
+/// Like random_state, with the given random number generator
+pub fn random_state_with<R: Rng + ?Sized>(rng: &mut R) -> Algorithm {
+    solve(&random_cube(rng)).inverse()
+}
+
+/// Returns a scramble of random moves, never turning a layer that was already turned since the
+/// last move on another axis, and stopping early when no move can follow the last ones
+pub fn random_moves(seed: Option<u64>, length: usize, moves: &[Rotation]) -> Algorithm {
+    random_moves_with(&mut rng(seed), length, moves)
+}
+
+/// Like random_moves, with the given random number generator
+pub fn random_moves_with<R: Rng + ?Sized>(
+    rng: &mut R,
+    length: usize,
+    moves: &[Rotation],
+) -> Algorithm {
+    let mut result: Vec<Rotation> = Vec::with_capacity(length);
+    while result.len() < length {
+        let allowed: Vec<Rotation> = moves
+            .iter()
+            .copied()
+            .filter(|&rot| !solution::is_rot_useless(&result, rot))
+            .collect();
+        match allowed.choose(rng) {
+            Some(&rot) => result.push(rot),
+            None => break,
+        }
+    }
+    result.into()
+}
+
+/// Quarter and half turns of the outer layers
+pub fn face_turns() -> Vec<Rotation> {
+    Rotation::iter()
+        .filter(|rot| !rot.is_cube_rotation() && !rot.is_slice() && !rot.is_wide())
+        .collect()
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SubsetErr {
+    #[error(transparent)]
+    Parse(#[from] ParseErr),
+    #[error("Cube rotation {0} in a subset, expected moves")]
+    CubeRotation(Rotation),
+    #[error("Empty subset")]
+    Empty,
+}
+
+/// Reads a subset of moves like `<R,U>` or `R U M`, returning every turn of the layers
+/// of each of them, which can't be every layer of an axis like with cube rotations
+pub fn parse_subset(subset: &str) -> Result<Vec<Rotation>, SubsetErr> {
+    let subset = subset.trim();
+    let subset = subset
+        .strip_prefix('<')
+        .and_then(|it| it.strip_suffix('>'))
+        .unwrap_or(subset);
+
+    let mut result = Vec::new();
+    for generator in subset.split([',', ' ']).filter(|it| !it.is_empty()) {
+        let generator = Rotation::from_str(generator)?;
+        if generator.layers() == 0b111 {
+            return Err(SubsetErr::CubeRotation(generator));
+        }
+        for turns in [1, -1, 2] {
+            let rot = Rotation::from_layers(generator.axis(), generator.layers(), turns);
+            if let Some(rot) = rot.filter(|rot| !result.contains(rot)) {
+                result.push(rot);
+            }
+        }
+    }
+
+    if result.is_empty() {
+        return Err(SubsetErr::Empty);
+    }
+    Ok(result)
+}
+
+/// Returns a cube picked uniformly out of every cube with the first two layers solved
+pub fn random_last_layer_cube<R: Rng + ?Sized>(rng: &mut R) -> CubieCube {
+    let mut cube = CubieCube::SOLVED;
+    // the first 4 corners and edges are the ones of the U layer
+    cube.cp[..4].shuffle(rng);
+    cube.ep[..4].shuffle(rng);
+    if cube.corner_parity() != cube.edge_parity() {
+        cube.ep.swap(0, 1);
+    }
+
+    for co in &mut cube.co[..3] {
+        *co = rng.gen_range(0..3);
+    }
+    cube.co[3] = (3 - cube.co[..3].iter().sum::<u8>() % 3) % 3;
+    for eo in &mut cube.eo[..3] {
+        *eo = rng.gen_range(0..2);
+    }
+    cube.eo[3] = cube.eo[..3].iter().sum::<u8>() % 2;
+
+    cube
+}
+
+/// Returns a scramble reaching a uniformly random last layer case (any OLL and PLL
+/// with any U turn) on a cube with the first two layers solved
+pub fn last_layer(seed: Option<u64>) -> Algorithm {
+    last_layer_with(&mut rng(seed))
+}
+
+/// Like last_layer, with the given random number generator
+pub fn last_layer_with<R: Rng + ?Sized>(rng: &mut R) -> Algorithm {
+    solve(&random_last_layer_cube(rng)).inverse()
+}
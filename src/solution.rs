@@ -1,7 +1,8 @@
+use crate::algorithm::Algorithm;
 use crate::cube::CubeState;
 use crate::rotation::Rotation;
 
-pub type Solution = Vec<Rotation>;
+pub type Solution = Algorithm;
 
 pub fn has_useless_moves(initial_state: CubeState, solution: &[Rotation]) -> bool {
     if solution.len() <= 1 {
@@ -26,38 +27,34 @@ pub fn has_useless_moves(initial_state: CubeState, solution: &[Rotation]) -> boo
     false
 }
 
+/// A rotation is useless if one of the layers it turns was already turned since the last
+/// move on a different axis, e.g. `R R`, `R2 R`, `R L R'` or `M R M'`, since those can always
+/// be written with fewer moves.
+///
+/// It is also useless if, together with those moves, every layer of the axis was turned, e.g.
+/// `r L'` or `R M L`, since that only differs from a shorter sequence by a whole cube rotation.
 pub fn is_rot_useless(solution: &[Rotation], rot: Rotation) -> bool {
-    if solution.is_empty() {
-        return false;
-    }
-
-    let face = rot.face();
-    let mut fnet = if rot.is_prime() { -1i8 } else { 1i8 };
-    let mut ftot = 1i8;
-    let mut onet = 0i8;
-    let mut otot = 0i8;
-
-    for &rot in solution.iter().rev() {
-        if rot.face() != face && rot.opposite_face() != face {
-            break;
-        }
-
-        if rot.face() == face {
-            ftot += 1;
-            if rot.is_prime() {
-                fnet -= 1;
-            } else {
-                fnet += 1;
-            }
-        } else {
-            otot += 1;
-            if rot.is_prime() {
-                onet -= 1;
-            } else {
-                onet += 1;
-            }
+    let mut turned_layers = rot.layers();
+
+    for prev in solution
+        .iter()
+        .rev()
+        .take_while(|prev| prev.axis() == rot.axis())
+    {
+        if prev.layers() & rot.layers() != 0 {
+            return true;
         }
+        turned_layers |= prev.layers();
     }
 
-    (fnet.abs() != ftot || ftot > 2) || (onet.abs() != otot || otot > 2)
+    turned_layers == 0b111
+}
+
+/// Like is_rot_useless, and also when the rotation commutes with the last one and comes before
+/// it in the rotation order, so that only one order of moves on the same axis is searched
+pub(crate) fn is_rot_redundant(solution: &[Rotation], rot: Rotation) -> bool {
+    is_rot_useless(solution, rot)
+        || solution
+            .last()
+            .is_some_and(|&prev| prev.axis() == rot.axis() && prev > rot)
 }
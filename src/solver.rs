@@ -1,220 +1,887 @@
-use std::collections::HashSet;
+use bon::builder;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 
+use crate::auf::Auf;
+use crate::cube::CellMask;
+use crate::face::Axis;
+use crate::ida::Ida;
+use crate::metric::Metric;
+use crate::pattern::CubePattern;
 use crate::solution;
 use crate::{cube::CubeState, rotation::Rotation, solution::Solution};
+use thiserror::Error;
+
+/// How the solver goes through the algorithms
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Method {
+    /// Meets in the middle, searching half of the moves from each side, which keeps every state
+    /// reached halfway in memory
+    #[default]
+    Bidirectional,
+    /// Iterative-deepening A*, searching from the initial state only and leaving the paths that
+    /// can't reach a desired state in time, which needs almost no memory but is slower on
+    /// long algorithms
+    Ida,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self {
+            Method::Bidirectional => "bidirectional",
+            Method::Ida => "ida",
+        };
+        write!(f, "{}", method)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Unknown method: {0}, expected bidirectional or ida")]
+pub struct UnknownMethod(String);
+
+impl FromStr for Method {
+    type Err = UnknownMethod;
+
+    /// Case insensitive, e.g. `ida` or `Bidirectional`
+    fn from_str(method: &str) -> Result<Method, Self::Err> {
+        match method.to_ascii_lowercase().as_str() {
+            "bidirectional" => Ok(Method::Bidirectional),
+            "ida" => Ok(Method::Ida),
+            _ => Err(UnknownMethod(method.to_string())),
+        }
+    }
+}
+
+/// Stops the searches it's given to, e.g. from another thread once the user gives up on them
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the searches, which return what they have found so far as incomplete
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
 
 ///
-/// Goes through all possible "rotation paths" in a DFS manner,
-/// stops when reaching a solution or path.len() == move_count/2 (meet in the middle)
+/// Keeps the pattern databases Method::Ida builds for some desired states, moves and metric,
+/// since searching them again with another move_count can use the same ones
 ///
-fn first_pass(
-    move_count: u8,
-    middle_states: &mut HashSet<CubeState>,
-    state: CubeState,
-    prev_states: &mut Vec<CubeState>,
-    path: &mut Vec<Rotation>,
-) {
-    if path.len() as u8 == move_count / 2 {
-        middle_states.insert(state);
-        return;
+/// Only the last ones are kept, until the cache is dropped. Without a cache, every search
+/// builds its own
+///
+#[derive(Default)]
+pub struct IdaCache(Mutex<Option<Arc<Ida>>>);
+
+impl IdaCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the databases of the last search if it had the same desired states, moves and
+    /// metric, or builds them unless the search is stopped first
+    fn get(
+        &self,
+        goals: &[CubePattern],
+        moves: &[Rotation],
+        metric: Metric,
+        stop: &Stop,
+    ) -> Option<Arc<Ida>> {
+        let mut last = self.0.lock().unwrap();
+        match &*last {
+            Some(ida) if ida.is_for(goals, moves, metric) => Some(ida.clone()),
+            _ => {
+                let ida = Arc::new(Ida::new(goals, moves, metric, stop)?);
+                Some(last.insert(ida).clone())
+            }
+        }
+    }
+}
+
+/// Tells the threads of a search to stop, once it's cancelled, runs out of time or has found
+/// enough algorithms
+pub(crate) struct Stop {
+    cancel: CancelToken,
+    stopped: AtomicBool,
+}
+
+impl Stop {
+    fn new(cancel: CancelToken) -> Self {
+        Stop {
+            cancel,
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    fn set(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_set(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed) || self.cancel.is_cancelled()
+    }
+}
+
+/// Algorithms found by a search, and whether it went through every path
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Solutions {
+    pub solutions: Vec<Solution>,
+    /// False when the search was cancelled, ran out of time or found max_solutions algorithms,
+    /// and there may be more
+    pub complete: bool,
+}
+
+/// The most a single move can cost, a slice half turn in QTM
+const MAX_ROTATION_COST: u8 = 4;
+
+/// Bytes a middle state takes in its set at most, the hash table keeping a control byte for
+/// each slot and up to about twice as many slots as states
+const MIDDLE_STATE_BYTES: usize = (std::mem::size_of::<CubeState>() + 1) * 2;
+
+/// Moves the first pass gets shallower at most to fit in memory, each one making the second
+/// pass about as many times slower as there are moves, past which IDA* is faster
+const MAX_REBALANCE: u8 = 2;
+
+/// Middle states of each cost offset with only the cells of each mask
+type MaskedStates = HashMap<(usize, CellMask), Arc<HashSet<CubeState>>>;
+
+/// States reached by the first pass, indexed by how much the path reaching them costs
+/// above move_count/2
+struct MiddleStates {
+    states: Vec<HashSet<CubeState>>,
+    /// The same states with only the cells specified in some partial state, built the first time
+    /// a state with those cells is looked up
+    masked: Mutex<MaskedStates>,
+}
+
+impl MiddleStates {
+    fn new() -> Self {
+        MiddleStates {
+            states: vec![HashSet::new(); MAX_ROTATION_COST as usize],
+            masked: Mutex::new(HashMap::new()),
+        }
     }
 
-    for rot in Rotation::iter() {
-        let new_state = state.rotate(rot);
+    fn insert(&mut self, cost_offset: usize, state: CubeState) {
+        self.states[cost_offset].insert(state);
+    }
 
-        if prev_states.iter().rev().any(|&x| x == new_state) {
-            continue;
+    fn extend(&mut self, other: MiddleStates) {
+        for (states, other) in self.states.iter_mut().zip(other.states) {
+            states.extend(other);
         }
+    }
 
-        if solution::is_rot_useless(path, rot) {
-            continue;
+    ///
+    /// Whether some state reached with the given cost offset matches the state, unspecified
+    /// stickers matching any color
+    ///
+    /// Since moves carry the unspecified stickers around, every state reached by the second pass
+    /// can have them in different places, and the middle states get indexed again for each one
+    /// of those, which makes partial desired states much slower to search and can take many
+    /// times the memory of the middle states
+    ///
+    fn contains(&self, cost_offset: usize, state: &CubeState) -> bool {
+        let Some(states) = self.states.get(cost_offset) else {
+            return false;
+        };
+        if !state.is_partial() {
+            return states.contains(state);
         }
 
-        path.push(rot);
-        prev_states.push(new_state);
-        first_pass(move_count, middle_states, new_state, prev_states, path);
-        prev_states.pop();
-        path.pop();
+        let mask = state.mask();
+        let masked = self
+            .masked
+            .lock()
+            .unwrap()
+            .entry((cost_offset, mask))
+            .or_insert_with(|| Arc::new(states.iter().map(|it| it.masked(&mask)).collect()))
+            .clone();
+        masked.contains(state)
+    }
+}
+
+///
+/// Paths the first pass goes through when stopping at the given cost, counted by the axis of
+/// the moves they end with and the layers those moves turn to skip the same useless moves
+///
+/// Different paths can reach the same state, so it's an upper bound of the middle states
+///
+fn count_middle_paths(moves: &[Rotation], metric: Metric, forward: u8) -> f64 {
+    let mut paths: Vec<HashMap<Option<(Axis, u8)>, f64>> =
+        vec![HashMap::new(); (forward + MAX_ROTATION_COST) as usize];
+    paths[0].insert(None, 1.0);
+
+    for cost in 0..forward as usize {
+        for (last, count) in std::mem::take(&mut paths[cost]) {
+            for &rot in moves {
+                let turned = match last {
+                    Some((axis, layers)) if axis == rot.axis() => {
+                        if layers & rot.layers() != 0 {
+                            continue;
+                        }
+                        layers | rot.layers()
+                    }
+                    _ => rot.layers(),
+                };
+                if turned == 0b111 {
+                    continue;
+                }
+
+                let next = cost + metric.rotation_cost(rot) as usize;
+                *paths[next].entry(Some((rot.axis(), turned))).or_default() += count;
+            }
+        }
     }
+    paths.iter().flat_map(|it| it.values()).sum()
 }
 
 ///
-/// Goes through all possible "rotation paths" in a DFS manner
-/// stops when reaching a solution (doesnt save this time) or when reaching
-/// a previously reached state or path.len() > (max_moves+1)/2 (meet in the middle)
+/// Cost the first pass stops at, half of move_count unless the middle states could take more
+/// than max_memory bytes, then less, or None when even MAX_REBALANCE moves less don't fit
 ///
-/// When it reaches a state saved in the middle states, it generates a new solver from the
-/// initial state to the current state, with a halved move count. Since the complexity of
-/// solving grows exponentially with the move count, recursively halving it should have a
-/// negligible peformance impact, and saves a lot of memory by not having to store the path to
-/// each middle state
+/// Threads fill their own middle states before they are merged, which takes about twice
+/// the memory
 ///
-fn second_pass(
+fn forward_cost(
+    moves: &[Rotation],
+    metric: Metric,
     move_count: u8,
-    middle_states: &HashSet<CubeState>,
-    found_solutions: &mut Vec<Solution>,
+    multi_threaded: bool,
+    max_memory: Option<usize>,
+) -> Option<u8> {
+    let half = move_count / 2;
+    let Some(max_memory) = max_memory else {
+        return Some(half);
+    };
+
+    let copies = if multi_threaded { 2.0 } else { 1.0 };
+    // the threads of the first pass start after one move, so it can't stop before it
+    (half.saturating_sub(MAX_REBALANCE).max(1)..=half.max(1))
+        .rev()
+        .find(|&forward| {
+            let paths = count_middle_paths(moves, metric, forward);
+            paths * (MIDDLE_STATE_BYTES as f64) * copies <= max_memory as f64
+        })
+}
+
+/// Parameters shared by every step of a search
+#[derive(Clone)]
+struct Search {
+    moves: Vec<Rotation>,
     initial_state: CubeState,
-    state: CubeState,
-    prev_states: &mut Vec<CubeState>,
-    path: &mut Vec<Rotation>,
-) {
-    if (path.len() as u8) == (move_count + 1) / 2 {
-        if !middle_states.contains(&state) {
+    move_count: u8,
+    metric: Metric,
+    /// Cost the first pass stops at, and the second pass goes up to move_count - forward
+    forward: u8,
+    stop: Arc<Stop>,
+}
+
+impl Search {
+    fn cost(&self, rot: Rotation) -> u8 {
+        self.metric.rotation_cost(rot) as u8
+    }
+
+    /// The same search with fewer moves, with a first pass that isn't any deeper so that it
+    /// fits in memory as well
+    fn shorter(&self, move_count: u8) -> Search {
+        Search {
+            move_count,
+            forward: (move_count / 2).min(self.forward),
+            ..self.clone()
+        }
+    }
+
+    ///
+    /// Goes through all possible "rotation paths" in a DFS manner,
+    /// stops when the path costs at least forward, move_count/2 unless that takes too much
+    /// memory (meet in the middle)
+    ///
+    /// Since moves can cost more than one, the middle is crossed with a cost anywhere between
+    /// forward and forward + MAX_ROTATION_COST - 1, so the states are saved along with it
+    ///
+    fn first_pass(
+        &self,
+        middle_states: &mut MiddleStates,
+        state: CubeState,
+        prev_states: &mut Vec<CubeState>,
+        path: &mut Vec<Rotation>,
+        cost: u8,
+    ) {
+        if self.stop.is_set() {
+            return;
+        }
+        if cost >= self.forward {
+            if cost <= self.move_count {
+                middle_states.insert((cost - self.forward) as usize, state);
+            }
             return;
         }
 
-        let l_solutions = solve(initial_state, state, move_count / 2, false);
-        let right: Vec<Rotation> = path.iter().map(|it| it.reverse()).rev().collect();
+        for &rot in &self.moves {
+            let new_state = state.rotate(rot);
+
+            if prev_states.iter().rev().any(|&x| x == new_state) {
+                continue;
+            }
+
+            if solution::is_rot_useless(path, rot) {
+                continue;
+            }
+
+            path.push(rot);
+            prev_states.push(new_state);
+            self.first_pass(
+                middle_states,
+                new_state,
+                prev_states,
+                path,
+                cost + self.cost(rot),
+            );
+            prev_states.pop();
+            path.pop();
+        }
+    }
+
+    ///
+    /// Goes through all possible "rotation paths" in a DFS manner
+    /// stops when reaching a solution (doesnt save this time) or when reaching
+    /// a previously reached state or the path costs more than move_count - forward
+    /// (meet in the middle)
+    ///
+    fn second_pass(
+        &self,
+        middle_states: &MiddleStates,
+        found: &mut dyn FnMut(Solution),
+        state: CubeState,
+        prev_states: &mut Vec<CubeState>,
+        path: &mut Vec<Rotation>,
+        cost: u8,
+    ) {
+        if self.stop.is_set() {
+            return;
+        }
+        self.meet(middle_states, found, state, path, cost);
+
+        for &rot in &self.moves {
+            let new_cost = cost + self.cost(rot);
+            if new_cost > self.move_count - self.forward {
+                continue;
+            }
+
+            let new_state = state.rotate(rot);
+
+            // a partial state stands for every state matching it, so reaching the same one again
+            // doesn't mean the path goes through the same state twice, e.g. when the moves only
+            // move unspecified stickers around, and the joined path is checked for it instead
+            if !new_state.is_partial() && prev_states.iter().rev().any(|&x| x == new_state) {
+                continue;
+            }
+
+            if solution::is_rot_useless(path, rot) {
+                continue;
+            }
+
+            prev_states.push(new_state);
+            path.push(rot);
+            self.second_pass(middle_states, found, new_state, prev_states, path, new_cost);
+            path.pop();
+            prev_states.pop();
+        }
+    }
+
+    ///
+    /// When the second pass reaches a state saved in the middle states with the remaining cost,
+    /// it generates a new solver from the initial state to the current state, with a smaller
+    /// move count. Since the complexity of solving grows exponentially with the move count,
+    /// recursively halving it should have a negligible peformance impact, and saves a lot of
+    /// memory by not having to store the path to each middle state
+    ///
+    fn meet(
+        &self,
+        middle_states: &MiddleStates,
+        found: &mut dyn FnMut(Solution),
+        state: CubeState,
+        path: &[Rotation],
+        cost: u8,
+    ) {
+        let left_cost = self.move_count - cost;
+        if left_cost < self.forward
+            || !middle_states.contains((left_cost - self.forward) as usize, &state)
+        {
+            return;
+        }
+
+        let l_solutions = if left_cost < self.move_count {
+            self.shorter(left_cost).run(state, false)
+        } else {
+            // the whole path was crossed in the first pass, strip its last move so the
+            // recursion still gets smaller
+            self.moves
+                .iter()
+                .filter(|&&rot| self.cost(rot) <= left_cost)
+                .flat_map(|&rot| {
+                    self.shorter(left_cost - self.cost(rot))
+                        .run(state.rotate(rot.reverse()), false)
+                        .into_iter()
+                        .map(move |left| left + &Solution::from(vec![rot]))
+                })
+                .collect()
+        };
+        let right: Solution = path.iter().map(|it| it.reverse()).rev().collect();
 
         for left in l_solutions {
-            let mut union = left;
-            union.append(&mut right.clone());
-            if !solution::has_useless_moves(initial_state, &union) {
-                found_solutions.push(union);
+            let union = left + &right;
+            if !solution::has_useless_moves(self.initial_state, &union) {
+                found(union);
             }
         }
+    }
 
-        return;
+    fn run(&self, desired_state: CubeState, multi_threaded: bool) -> Vec<Solution> {
+        let mut found_solutions = Vec::new();
+        self.run_any(&[desired_state], multi_threaded, &mut |solution| {
+            found_solutions.push(solution)
+        });
+        dedup(found_solutions)
     }
 
-    for rot in Rotation::iter() {
-        let new_state = state.rotate(rot);
+    /// Finds the algorithms reaching any of the desired states, sharing the first pass
+    /// between all of them, and passes each one to found as soon as it's found, some of them
+    /// more than once
+    fn run_any(
+        &self,
+        desired_states: &[CubeState],
+        multi_threaded: bool,
+        found: &mut dyn FnMut(Solution),
+    ) {
+        let initial_state = self.initial_state;
+        let is_desired = |state: CubeState| desired_states.iter().any(|it| state.matches(it));
 
-        if prev_states.iter().rev().any(|&x| x == new_state) {
-            continue;
+        // --- Edge cases
+        if self.move_count == 0u8 {
+            if is_desired(initial_state) {
+                found(Solution::new());
+            }
+            return;
         }
 
-        if solution::is_rot_useless(path, rot) {
-            continue;
+        if self.move_count == 1u8 {
+            for &rot in &self.moves {
+                let state = initial_state.rotate(rot);
+                if self.cost(rot) == 1 && is_desired(state) {
+                    found(vec![rot].into());
+                }
+            }
+            return;
         }
+        // ---
 
-        prev_states.push(new_state);
-        path.push(rot);
-        second_pass(
-            move_count,
-            middle_states,
-            found_solutions,
-            initial_state,
-            new_state,
-            prev_states,
-            path,
-        );
-        path.pop();
-        prev_states.pop();
+        if !multi_threaded {
+            let mut middle_states = MiddleStates::new();
+            self.first_pass(
+                &mut middle_states,
+                initial_state,
+                &mut vec![initial_state],
+                &mut Vec::new(),
+                0,
+            );
+            for &desired_state in desired_states {
+                self.second_pass(
+                    &middle_states,
+                    found,
+                    desired_state,
+                    &mut vec![desired_state],
+                    &mut Vec::new(),
+                    0,
+                );
+            }
+            return;
+        }
+
+        let handlers = self
+            .moves
+            .iter()
+            .map(|&rot| {
+                let search = self.clone();
+                thread::spawn(move || {
+                    let state = initial_state.rotate(rot);
+                    let mut middle_states = MiddleStates::new();
+                    search.first_pass(
+                        &mut middle_states,
+                        state,
+                        &mut vec![initial_state, state],
+                        &mut vec![rot],
+                        search.cost(rot),
+                    );
+
+                    middle_states
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut middle_states = MiddleStates::new();
+        handlers
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .for_each(|generated| middle_states.extend(generated));
+        let middle_states = &middle_states;
+
+        // the threads send what they find to this one, which passes it on as it arrives
+        let max_right_cost = self.move_count - self.forward;
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            // one thread per move like the first pass, however many desired states there are
+            for &rot in &self.moves {
+                if self.cost(rot) > max_right_cost {
+                    continue;
+                }
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    for &desired_state in desired_states {
+                        let state = desired_state.rotate(rot);
+                        self.second_pass(
+                            middle_states,
+                            &mut |solution| {
+                                let _ = sender.send(solution);
+                            },
+                            state,
+                            &mut vec![desired_state, state],
+                            &mut vec![rot],
+                            self.cost(rot),
+                        );
+                    }
+                });
+            }
+            drop(sender);
+
+            for &desired_state in desired_states {
+                self.meet(middle_states, found, desired_state, &[], 0);
+            }
+            receiver.into_iter().for_each(found);
+        });
     }
 }
 
+/// Paths costing more than one per move can be split in the middle in more than one way,
+/// and close enough desired states can be reached by the same path, which finds the same
+/// solution more than once
+fn dedup(mut solutions: Vec<Solution>) -> Vec<Solution> {
+    solutions.sort();
+    solutions.dedup();
+    solutions
+}
+
+/// Returns the moves the solver is allowed to use
+fn search_moves(slice_moves: bool, wide_moves: bool) -> Vec<Rotation> {
+    Rotation::iter()
+        .filter(|rot| !rot.is_cube_rotation())
+        .filter(|rot| slice_moves || !rot.is_slice())
+        .filter(|rot| wide_moves || !rot.is_wide())
+        .collect()
+}
+
+///
+/// Finds every algorithm of exactly move_count moves going from initial_state to desired_state,
+/// with the moves counted in the given metric, by default HTM, or STM with slice_moves so that
+/// every slice counts as a single move
+///
+/// Unspecified (N) stickers of desired_state match any color, so any state reached with the
+/// rest of the stickers in place is a solution. Unspecified stickers of initial_state only
+/// match unspecified ones. desired_state can also be a pattern with a set of colors for
+/// each sticker, which is searched with those stickers unspecified, keeping the algorithms
+/// reaching one of the colors of each
+///
+/// Searches with the outer layer moves (quarter and half turns) and, when slice_moves or
+/// wide_moves are set, with the M, E and S slices or the wide moves as well
+///
+/// With auf set, any U turn of the initial state, the desired state or both is the same case,
+/// and the algorithms don't spend moves turning the U layer at the start or the end.
+/// Auf::adjustments tells which U turns each of them assumes
+///
+/// The bidirectional method is the fastest, but keeps every state reached halfway in memory,
+/// which grows exponentially with move_count. Method::Ida needs almost no memory instead,
+/// and finds the same algorithms. Its pattern databases are built for each search, unless
+/// they are in ida_cache. Without method, desired states with unspecified stickers or sets of
+/// colors use Method::Ida, the bidirectional method keeping a copy of the states reached
+/// halfway for each set of cells they end up specifying, and the others the bidirectional one
+///
+/// With max_memory, the bidirectional method estimates how many bytes the states reached
+/// halfway take, and when it's more, stops up to 2 moves before halfway and searches that
+/// many more moves from the desired states, or uses Method::Ida when that isn't enough.
+/// Partial desired states always use Method::Ida then, the copies of those states they are
+/// looked up in depending on how the search moves their unspecified stickers around
+///
+/// The search stops early once cancel is cancelled, it has run for timeout or found
+/// max_solutions algorithms, every thread finishing the step it is in, and returns the
+/// algorithms found so far as incomplete. Building the databases of Method::Ida counts as
+/// part of the search
+///
+#[builder]
 pub fn solve(
     initial_state: CubeState,
-    desired_state: CubeState,
+    #[builder(into)] desired_state: CubePattern,
     move_count: u8,
-    multi_threaded: bool,
-) -> Vec<Solution> {
-    // --- Edge cases
-    if move_count == 0u8 {
-        if initial_state == desired_state {
-            return vec![vec![]];
-        } else {
-            return vec![];
-        }
+    #[builder(default)] multi_threaded: bool,
+    #[builder(default)] slice_moves: bool,
+    #[builder(default)] wide_moves: bool,
+    metric: Option<Metric>,
+    #[builder(default)] auf: Auf,
+    method: Option<Method>,
+    max_memory: Option<usize>,
+    #[builder(default)] cancel: CancelToken,
+    timeout: Option<Duration>,
+    max_solutions: Option<usize>,
+    ida_cache: Option<&IdaCache>,
+) -> Solutions {
+    solve_any()
+        .initial_state(initial_state)
+        .desired_states(&[desired_state])
+        .move_count(move_count)
+        .multi_threaded(multi_threaded)
+        .slice_moves(slice_moves)
+        .wide_moves(wide_moves)
+        .maybe_metric(metric)
+        .auf(auf)
+        .maybe_method(method)
+        .maybe_max_memory(max_memory)
+        .cancel(cancel)
+        .maybe_timeout(timeout)
+        .maybe_max_solutions(max_solutions)
+        .maybe_ida_cache(ida_cache)
+        .call()
+}
+
+///
+/// Like solve, but finds every algorithm reaching any of the desired states, e.g. any state
+/// solved up to the last U move or any of a set of PLLs
+///
+/// The first half of the search is shared between all of them, so searching for several
+/// desired states at once is faster than searching for each one of them
+///
+#[builder]
+pub fn solve_any(
+    initial_state: CubeState,
+    desired_states: &[CubePattern],
+    move_count: u8,
+    #[builder(default)] multi_threaded: bool,
+    #[builder(default)] slice_moves: bool,
+    #[builder(default)] wide_moves: bool,
+    metric: Option<Metric>,
+    #[builder(default)] auf: Auf,
+    method: Option<Method>,
+    max_memory: Option<usize>,
+    #[builder(default)] cancel: CancelToken,
+    timeout: Option<Duration>,
+    max_solutions: Option<usize>,
+    ida_cache: Option<&IdaCache>,
+) -> Solutions {
+    let mut solutions = Vec::new();
+    let flow = solve_any_each()
+        .initial_state(initial_state)
+        .desired_states(desired_states)
+        .move_count(move_count)
+        .multi_threaded(multi_threaded)
+        .slice_moves(slice_moves)
+        .wide_moves(wide_moves)
+        .maybe_metric(metric)
+        .auf(auf)
+        .maybe_method(method)
+        .maybe_max_memory(max_memory)
+        .cancel(cancel)
+        .maybe_timeout(timeout)
+        .maybe_max_solutions(max_solutions)
+        .maybe_ida_cache(ida_cache)
+        .on_solution(|solution| {
+            solutions.push(solution);
+            ControlFlow::Continue(())
+        })
+        .call();
+
+    solutions.sort();
+    Solutions {
+        solutions,
+        complete: flow.is_continue(),
     }
+}
 
-    if move_count == 1u8 {
-        let mut found_solutions = Vec::new();
-        for rot in Rotation::iter() {
-            let state = initial_state.rotate(rot);
-            if state == desired_state {
-                found_solutions.push(vec![rot]);
+/// Runs the search, stopping it once it takes longer than timeout
+fn with_timeout<T>(stop: &Stop, timeout: Option<Duration>, search: impl FnOnce() -> T) -> T {
+    let Some(timeout) = timeout else {
+        return search();
+    };
+
+    thread::scope(|scope| {
+        // dropping done wakes the timer up as soon as the search is over
+        let (done, timer) = mpsc::channel::<()>();
+        scope.spawn(move || {
+            if timer.recv_timeout(timeout) == Err(mpsc::RecvTimeoutError::Timeout) {
+                stop.set();
             }
+        });
+        let result = search();
+        drop(done);
+        result
+    })
+}
+
+///
+/// Like solve_any, but passes each algorithm to on_solution as soon as it's found instead of
+/// returning all of them at the end, in no particular order and each one once
+///
+/// on_solution returning ControlFlow::Break stops the search like max_solutions does. Returns
+/// ControlFlow::Break when the search stopped early, for any reason
+///
+#[builder]
+pub fn solve_any_each(
+    initial_state: CubeState,
+    desired_states: &[CubePattern],
+    move_count: u8,
+    #[builder(default)] multi_threaded: bool,
+    #[builder(default)] slice_moves: bool,
+    #[builder(default)] wide_moves: bool,
+    metric: Option<Metric>,
+    #[builder(default)] auf: Auf,
+    method: Option<Method>,
+    max_memory: Option<usize>,
+    #[builder(default)] cancel: CancelToken,
+    timeout: Option<Duration>,
+    max_solutions: Option<usize>,
+    ida_cache: Option<&IdaCache>,
+    mut on_solution: impl FnMut(Solution) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    // the algorithm followed by a U turn reaching a desired state is the same as the
+    // algorithm reaching that state with the opposite U turn
+    let desired_states: Vec<CubePattern> = desired_states
+        .iter()
+        .flat_map(|&desired_state| {
+            auf.post_turns()
+                .iter()
+                .map(move |post| post.map_or(desired_state, |rot| desired_state.rotate(rot)))
+        })
+        .collect();
+
+    let mut relaxed_states: Vec<CubeState> = Vec::with_capacity(desired_states.len());
+    for desired_state in &desired_states {
+        let relaxed = desired_state.relaxed();
+        if !relaxed_states.contains(&relaxed) {
+            relaxed_states.push(relaxed);
         }
-        return found_solutions;
     }
-    // ---
 
-    if !multi_threaded {
-        let mut middle_states = HashSet::new();
-        let mut found_solutions = Vec::new();
-        first_pass(
-            move_count,
-            &mut middle_states,
-            initial_state,
-            &mut vec![initial_state],
-            &mut Vec::new(),
-        );
-        second_pass(
-            move_count,
-            &middle_states,
-            &mut found_solutions,
-            initial_state,
-            desired_state,
-            &mut vec![desired_state],
-            &mut Vec::new(),
-        );
-        return found_solutions;
-    }
-
-    let handlers = Rotation::iter()
-        .map(|rot| {
-            thread::spawn(move || {
-                let state = initial_state.rotate(rot);
-                let mut middle_states: HashSet<CubeState> = HashSet::new();
-                first_pass(
-                    move_count,
-                    &mut middle_states,
-                    state,
-                    &mut vec![initial_state, state],
-                    &mut vec![rot],
-                );
+    let metric = metric.unwrap_or(if slice_moves {
+        Metric::Stm
+    } else {
+        Metric::Htm
+    });
+    let moves = search_moves(slice_moves, wide_moves);
+    // the bidirectional method looks partial states up in a copy of the middle states for
+    // each set of specified cells, which takes more memory than the middle states themselves
+    let partial = relaxed_states.iter().any(|it| it.is_partial());
+    let method = method.unwrap_or(if partial {
+        Method::Ida
+    } else {
+        Method::Bidirectional
+    });
+    // without a first pass fitting in memory, IDA* is the only method that does
+    let forward = match method {
+        // the copies of the middle states partial states are looked up in aren't bounded
+        Method::Bidirectional if partial && max_memory.is_some() => None,
+        Method::Bidirectional => {
+            forward_cost(&moves, metric, move_count, multi_threaded, max_memory)
+        }
+        Method::Ida => None,
+    };
+    let mut ida = None;
 
-                middle_states
-            })
-        })
-        .collect::<Vec<_>>();
-
-    let mut middle_states = HashSet::new();
-    handlers
-        .into_iter()
-        .map(|h| h.join().unwrap())
-        .for_each(|generated| middle_states.extend(generated));
-
-    struct MiddleStates(*const HashSet<CubeState>);
-    unsafe impl Send for MiddleStates {}
-    unsafe impl Sync for MiddleStates {}
-    impl AsRef<HashSet<CubeState>> for MiddleStates {
-        fn as_ref(&self) -> &HashSet<CubeState> {
-            unsafe { self.0.as_ref().unwrap() }
-        }
-    }
-
-    let handlers = Rotation::iter()
-        .map(|rot| {
-            let middle_states_ptr = MiddleStates(&middle_states);
-            thread::spawn(move || {
-                let state = desired_state.rotate(rot);
-                let mut found_solutions: Vec<Solution> = Vec::new();
-                let middle_states = middle_states_ptr.as_ref();
-                second_pass(
-                    move_count,
-                    middle_states,
-                    &mut found_solutions,
-                    initial_state,
-                    state,
-                    &mut vec![desired_state, state],
-                    &mut vec![rot],
-                );
+    let stop = Arc::new(Stop::new(cancel));
+    let enough = |count: usize| max_solutions.is_some_and(|max| count >= max);
+    let mut flow = if enough(0) {
+        stop.set();
+        ControlFlow::Break(())
+    } else {
+        ControlFlow::Continue(())
+    };
+    // paths costing more than one per move can be split in the middle in more than one way,
+    // and close enough desired states can be reached by the same path
+    let mut seen = HashSet::new();
 
-                found_solutions
-            })
-        })
-        .collect::<Vec<_>>();
+    with_timeout(&stop, timeout, || {
+        for pre in auf.pre_turns() {
+            if flow.is_break() {
+                break;
+            }
 
-    let mut found_solutions = Vec::new();
-    handlers
-        .into_iter()
-        .map(|h| h.join().unwrap())
-        .for_each(|generated| found_solutions.extend(generated));
+            let initial_state = pre.map_or(initial_state, |rot| initial_state.rotate(rot));
+            let mut found = |solution: Solution| {
+                if flow.is_break() || auf.wastes_moves(&solution) {
+                    return;
+                }
+                let state = solution.apply_to(initial_state);
+                if !desired_states.iter().any(|it| it.accepts(&state))
+                    || !seen.insert(solution.clone())
+                {
+                    return;
+                }
 
-    found_solutions
+                flow = on_solution(solution);
+                if enough(seen.len()) {
+                    flow = ControlFlow::Break(());
+                }
+                if flow.is_break() {
+                    stop.set();
+                }
+            };
+
+            match forward {
+                Some(forward) => {
+                    let search = Search {
+                        moves: moves.clone(),
+                        initial_state,
+                        move_count,
+                        metric,
+                        forward,
+                        stop: stop.clone(),
+                    };
+                    search.run_any(&relaxed_states, multi_threaded, &mut found);
+                }
+                None => {
+                    if ida.is_none() {
+                        ida = match ida_cache {
+                            Some(cache) => cache.get(&desired_states, &moves, metric, &stop),
+                            None => Ida::new(&desired_states, &moves, metric, &stop).map(Arc::new),
+                        };
+                    }
+                    match &ida {
+                        Some(ida) => {
+                            ida.run(initial_state, move_count, multi_threaded, &stop, &mut found)
+                        }
+                        // stopped while building the databases
+                        None => break,
+                    }
+                }
+            }
+        }
+    });
+
+    if stop.is_set() {
+        ControlFlow::Break(())
+    } else {
+        ControlFlow::Continue(())
+    }
 }
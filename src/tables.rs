@@ -0,0 +1,145 @@
+/*
+Tables the solvers build once and keep on disk
+
+Each table is a file in the cache directory, a header followed by its bytes. The header holds
+the version of the file format, the version of the table (changed whenever the way it is built
+changes), the length of the table and a CRC32 checksum of it. Later runs memory-map the file
+instead of building the table again, and build it again when the header doesn't match, which
+also catches files written halfway or damaged since.
+*/
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+
+use memmap2::Mmap;
+
+const MAGIC: &[u8; 8] = b"CUBERTBL";
+/// Version of the header and of how the bytes are laid out after it
+const FORMAT_VERSION: u32 = 1;
+/// Magic, format version, table version, length and checksum, padded to 8 bytes
+const HEADER_LEN: usize = 32;
+
+/// Environment variable overriding the default cache directory, caching nothing when empty
+pub const CACHE_DIR_VAR: &str = "CUBERITHM_CACHE_DIR";
+
+static CACHE_DIR: LazyLock<RwLock<Option<PathBuf>>> = LazyLock::new(|| {
+    let dir = match std::env::var_os(CACHE_DIR_VAR) {
+        Some(dir) if dir.is_empty() => None,
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => default_cache_dir(),
+    };
+    RwLock::new(dir)
+});
+
+#[cfg(not(test))]
+fn default_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("cuberithm"))
+}
+
+/// Tests keep their tables in the temporary directory, out of the user's cache
+#[cfg(test)]
+fn default_cache_dir() -> Option<PathBuf> {
+    Some(std::env::temp_dir().join("cuberithm-tests"))
+}
+
+/// Directory the tables are kept in, None when they are built on every run
+pub fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.read().unwrap().clone()
+}
+
+///
+/// Changes the directory the tables are kept in, None building them on every run instead
+///
+/// Only tables not loaded yet are affected
+///
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.write().unwrap() = dir;
+}
+
+/// Bytes of a table, either just built or mapped from its file
+pub(crate) enum Table {
+    Built(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Table {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Table::Built(bytes) => bytes,
+            Table::Mapped(mmap) => &mmap[HEADER_LEN..],
+        }
+    }
+}
+
+fn header(version: u32, bytes: &[u8]) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&version.to_le_bytes());
+    header[16..24].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+    header[24..28].copy_from_slice(&crc32fast::hash(bytes).to_le_bytes());
+    header
+}
+
+/// Maps the file of a table, if it has the given version and isn't damaged
+fn load(path: &Path, version: u32) -> Option<Mmap> {
+    let file = File::open(path).ok()?;
+    // SAFETY: the files are only ever replaced by renaming new ones over them, never written
+    // in place, so the mapped bytes don't change while they are used
+    let mmap = unsafe { Mmap::map(&file) }.ok()?;
+    if mmap.len() < HEADER_LEN {
+        return None;
+    }
+
+    let (found, bytes) = mmap.split_at(HEADER_LEN);
+    (found == header(version, bytes)).then_some(mmap)
+}
+
+/// Writes the file of a table next to where it goes before moving it there, so that other
+/// runs never see it halfway written
+fn store(path: &Path, version: u32, bytes: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&header(version, bytes))?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+///
+/// Returns the table with the given name from the directory, or builds it when its file is
+/// missing, has another version or is damaged, and writes it there for the next runs
+///
+/// Failing to write it only means building it again next time, so the error is ignored
+///
+pub(crate) fn load_or_build_in(
+    dir: Option<&Path>,
+    name: &str,
+    version: u32,
+    build: impl FnOnce() -> Vec<u8>,
+) -> Table {
+    let Some(path) = dir.map(|dir| dir.join(format!("{}.tbl", name))) else {
+        return Table::Built(build());
+    };
+
+    if let Some(mmap) = load(&path, version) {
+        return Table::Mapped(mmap);
+    }
+    let bytes = build();
+    let _ = store(&path, version, &bytes);
+    Table::Built(bytes)
+}
+
+/// Like load_or_build_in, in the cache directory
+pub(crate) fn load_or_build(name: &str, version: u32, build: impl FnOnce() -> Vec<u8>) -> Table {
+    load_or_build_in(cache_dir().as_deref(), name, version, build)
+}
@@ -0,0 +1,265 @@
+/*
+Kociemba's two-phase algorithm, solving any cube with face turns in a fraction of a second
+
+Phase 1 brings the cube into the group generated by U, D, R2, L2, F2 and B2, where every piece
+is oriented and the edges of the E slice are in the E slice. Phase 2 solves it from there using
+only those moves. Each phase searches with IDA* over coordinates (numbers describing just the
+part of the cube the phase cares about), with tables of how every move changes them and of how
+many moves at least are left from each pair of them.
+
+Phase 1 is searched with more and more moves, each of its solutions followed by the shortest
+phase 2, until the whole solution is short enough. Both phases being optimal doesn't make the
+whole solution optimal, but going on for long enough always finds a near optimal one.
+*/
+
+use bon::builder;
+use std::sync::LazyLock;
+
+use crate::algorithm::Algorithm;
+use crate::coord::*;
+use crate::cube::CubeState;
+use crate::cubie::{CubieCube, SolveErr};
+use crate::rotation::Rotation;
+use crate::solution::{self, Solution};
+use crate::tables::{self, Table};
+
+/// Version of the pruning tables kept on disk, to change along with how they are built
+const TABLE_VERSION: u32 = 1;
+
+/// Longest phase 2 needed for any cube
+const MAX_PHASE2: u8 = 18;
+
+/// Moves solutions take at most by default, short enough to be near optimal and long enough
+/// to be found in a fraction of a second for any cube
+pub const DEFAULT_MAX_LENGTH: u8 = 21;
+
+/// Moves keeping the cube in the phase 2 group
+const PHASE2_MOVES: [Rotation; 10] = [
+    Rotation::U,
+    Rotation::Up,
+    Rotation::U2,
+    Rotation::D,
+    Rotation::Dp,
+    Rotation::D2,
+    Rotation::R2,
+    Rotation::L2,
+    Rotation::F2,
+    Rotation::B2,
+];
+
+/// Pruning table from the cache directory, built when it isn't there
+fn cached_pruning_table<const M: usize>(
+    name: &str,
+    a_moves: &[[u16; M]],
+    b_moves: &[[u16; M]],
+) -> Table {
+    tables::load_or_build(name, TABLE_VERSION, || pruning_table(a_moves, b_moves))
+}
+
+/// Fewest moves solving each pair of coordinates, indexed by a * size_b + b
+fn pruning_table<const M: usize>(a_moves: &[[u16; M]], b_moves: &[[u16; M]]) -> Vec<u8> {
+    let size_b = b_moves.len();
+    let mut table = vec![u8::MAX; a_moves.len() * size_b];
+    table[0] = 0;
+
+    let mut depth = 0;
+    let mut filled = 1;
+    while filled < table.len() {
+        for idx in 0..table.len() {
+            if table[idx] != depth {
+                continue;
+            }
+            let (a, b) = (idx / size_b, idx % size_b);
+            for m in 0..M {
+                let next = a_moves[a][m] as usize * size_b + b_moves[b][m] as usize;
+                if table[next] == u8::MAX {
+                    table[next] = depth + 1;
+                    filled += 1;
+                }
+            }
+        }
+        depth += 1;
+    }
+    table
+}
+
+struct Tables {
+    twist_moves: Vec<[u16; 18]>,
+    flip_moves: Vec<[u16; 18]>,
+    slice_moves: Vec<[u16; 18]>,
+    corner_perm_moves: Vec<[u16; 10]>,
+    edge8_perm_moves: Vec<[u16; 10]>,
+    slice_perm_moves: Vec<[u16; 10]>,
+    twist_slice_pruning: Table,
+    flip_slice_pruning: Table,
+    corner_perm_slice_pruning: Table,
+    edge8_perm_slice_pruning: Table,
+}
+
+static TABLES: LazyLock<Tables> = LazyLock::new(|| {
+    let twist_moves = move_table(TWISTS, &FACE_TURNS, set_twist, twist);
+    let flip_moves = move_table(FLIPS, &FACE_TURNS, set_flip, flip);
+    let slice_moves = move_table(SLICES, &FACE_TURNS, set_slice, slice);
+    let corner_perm_moves = move_table(CORNER_PERMS, &PHASE2_MOVES, set_corner_perm, corner_perm);
+    let edge8_perm_moves = move_table(EDGE8_PERMS, &PHASE2_MOVES, set_edge8_perm, edge8_perm);
+    let slice_perm_moves = move_table(SLICE_PERMS, &PHASE2_MOVES, set_slice_perm, slice_perm);
+
+    Tables {
+        twist_slice_pruning: cached_pruning_table(
+            "two_phase_twist_slice",
+            &twist_moves,
+            &slice_moves,
+        ),
+        flip_slice_pruning: cached_pruning_table("two_phase_flip_slice", &flip_moves, &slice_moves),
+        corner_perm_slice_pruning: cached_pruning_table(
+            "two_phase_corner_perm_slice",
+            &corner_perm_moves,
+            &slice_perm_moves,
+        ),
+        edge8_perm_slice_pruning: cached_pruning_table(
+            "two_phase_edge8_perm_slice",
+            &edge8_perm_moves,
+            &slice_perm_moves,
+        ),
+        twist_moves,
+        flip_moves,
+        slice_moves,
+        corner_perm_moves,
+        edge8_perm_moves,
+        slice_perm_moves,
+    }
+});
+
+impl Tables {
+    fn phase1_distance(&self, twist: usize, flip: usize, slice: usize) -> u8 {
+        self.twist_slice_pruning[twist * SLICES + slice]
+            .max(self.flip_slice_pruning[flip * SLICES + slice])
+    }
+
+    fn phase2_distance(&self, corner_perm: usize, edge8_perm: usize, slice_perm: usize) -> u8 {
+        self.corner_perm_slice_pruning[corner_perm * SLICE_PERMS + slice_perm]
+            .max(self.edge8_perm_slice_pruning[edge8_perm * SLICE_PERMS + slice_perm])
+    }
+
+    /// Searches the phase 1 paths of exactly depth moves, then solves phase 2 after each one
+    /// without going over max_length moves in total
+    fn phase1(
+        &self,
+        cube: &CubieCube,
+        (twist, flip, slice): (usize, usize, usize),
+        depth: u8,
+        max_length: u8,
+        path: &mut Vec<Rotation>,
+    ) -> bool {
+        if depth == 0 {
+            return twist == 0
+                && flip == 0
+                && slice == 0
+                && self.solve_phase2(cube, max_length - path.len() as u8, path);
+        }
+
+        for (m, &rot) in FACE_TURNS.iter().enumerate() {
+            // ending with a phase 2 move means a shorter phase 1 was already tried
+            if (depth == 1 && PHASE2_MOVES.contains(&rot)) || solution::is_rot_redundant(path, rot)
+            {
+                continue;
+            }
+
+            let coords = (
+                self.twist_moves[twist][m] as usize,
+                self.flip_moves[flip][m] as usize,
+                self.slice_moves[slice][m] as usize,
+            );
+            if self.phase1_distance(coords.0, coords.1, coords.2) >= depth {
+                continue;
+            }
+
+            path.push(rot);
+            if self.phase1(cube, coords, depth - 1, max_length, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    /// Appends the shortest phase 2 solving the cube after the phase 1 path, if there is one
+    /// of at most max_depth moves
+    fn solve_phase2(&self, cube: &CubieCube, max_depth: u8, path: &mut Vec<Rotation>) -> bool {
+        let cube = path.iter().fold(*cube, |cube, &rot| cube.rotate(rot));
+        let coords = (corner_perm(&cube), edge8_perm(&cube), slice_perm(&cube));
+        (0..=max_depth.min(MAX_PHASE2)).any(|depth| self.phase2(coords, depth, path))
+    }
+
+    fn phase2(
+        &self,
+        (corner_perm, edge8_perm, slice_perm): (usize, usize, usize),
+        depth: u8,
+        path: &mut Vec<Rotation>,
+    ) -> bool {
+        if depth == 0 {
+            return corner_perm == 0 && edge8_perm == 0 && slice_perm == 0;
+        }
+
+        for (m, &rot) in PHASE2_MOVES.iter().enumerate() {
+            if solution::is_rot_redundant(path, rot) {
+                continue;
+            }
+
+            let coords = (
+                self.corner_perm_moves[corner_perm][m] as usize,
+                self.edge8_perm_moves[edge8_perm][m] as usize,
+                self.slice_perm_moves[slice_perm][m] as usize,
+            );
+            if self.phase2_distance(coords.0, coords.1, coords.2) >= depth {
+                continue;
+            }
+
+            path.push(rot);
+            if self.phase2(coords, depth - 1, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+}
+
+/// Returns face turns solving the cube in at most max_length moves, the first solution found
+/// with the shortest phase 1
+pub(crate) fn solve_cubie(cube: &CubieCube, max_length: u8) -> Option<Algorithm> {
+    let tables = &*TABLES;
+    let normalized = cube.normalized();
+    let coords = (twist(&normalized), flip(&normalized), slice(&normalized));
+
+    let mut path = Vec::new();
+    // every solution ends with its longest run of phase 2 moves, which stays in the phase 2
+    // group all along, so the moves before it are a phase 1 that doesn't end with a phase 2 move,
+    // and trying every length of phase 1 finds any solution of at most max_length moves
+    let found = (0..=max_length)
+        .any(|depth| tables.phase1(&normalized, coords, depth, max_length, &mut path));
+
+    found.then(|| {
+        path.into_iter()
+            .map(|rot| cube.turn_as_held(rot))
+            .collect::<Vec<_>>()
+            .into()
+    })
+}
+
+///
+/// Finds an algorithm of face turns solving a fully specified state in at most max_length moves
+/// (HTM), 21 by default, which takes a fraction of a second for any state. Shorter lengths take
+/// longer to find, exponentially so near the optimal one
+///
+/// The tables are built the first time the solver is used, which takes a fraction of a second
+/// in release builds, and the pruning ones are kept in the cache directory for the next runs
+///
+#[builder]
+pub fn solve(
+    state: CubeState,
+    #[builder(default = DEFAULT_MAX_LENGTH)] max_length: u8,
+) -> Result<Solution, SolveErr> {
+    let cube = CubieCube::solvable(state)?;
+    solve_cubie(&cube, max_length).ok_or(SolveErr::NotFound(max_length))
+}